@@ -1,11 +1,14 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueEnum};
 
 use std::io;
 
 #[derive(Debug)]
 enum Error {
-    ParseError,
-    EvaluateError,
+    Read,
+    Parse,
+    Evaluate,
+    Load,
+    Save,
 }
 
 #[derive(Parser)]
@@ -25,14 +28,112 @@ struct Args {
     )]
     show_ast: bool,
 
-    #[arg(help = "Script to execute. If not specified, enter interactive mode.")]
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Print the disassembled bytecode for each statement"
+    )]
+    show_bytecode: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Print the environment after each evaluation"
+    )]
+    show_environment: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Fold constant expressions and remove unreachable code before running"
+    )]
+    optimize: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Deny scripts access to the filesystem"
+    )]
+    no_filesystem: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Deny scripts access to stdin and stderr"
+    )]
+    no_console: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Print what the garbage collector has freed after each evaluation"
+    )]
+    gc_stats: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Run the garbage collector on every allocation, which is slow"
+    )]
+    gc_stress: bool,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = EngineKind::Evaluator,
+        help = "How programs are executed"
+    )]
+    engine: EngineKind,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "script",
+        help = "Compile the script and save its bytecode to PATH instead of running it"
+    )]
+    save_bytecode: Option<std::path::PathBuf>,
+
+    #[arg(
+        help = "Script to execute, as source or saved bytecode. If not specified, enter interactive mode."
+    )]
     script: Option<std::path::PathBuf>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum EngineKind {
+    /// Walk the syntax tree
+    Evaluator,
+    /// Compile to bytecode and run it on the virtual machine
+    Vm,
+}
+
+/* the evaluator recurses on the stack for every call a script makes, so scripts run on a thread
+ * with room for `Limits::DEFAULT_MAX_CALL_DEPTH` calls even in a debug build */
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+
 fn main() -> std::process::ExitCode {
-    match run(Args::parse()) {
+    let args = Args::parse();
+
+    /* the virtual machine frees values by reference counting alone */
+    if args.engine == EngineKind::Vm && (args.gc_stats || args.gc_stress) {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--gc-stats and --gc-stress need the evaluator, as the virtual machine has no \
+                 garbage collector",
+            )
+            .exit();
+    }
+    let result = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(args))
+        .expect("failed to spawn the interpreter thread")
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+
+    match result {
         Ok(()) => std::process::ExitCode::SUCCESS,
-        Err(error) => std::process::ExitCode::FAILURE,
+        Err(_) => std::process::ExitCode::FAILURE,
     }
 }
 
@@ -49,6 +150,62 @@ struct Interpreter {
     mode: Mode,
     show_tokens: bool,
     show_ast: bool,
+    show_bytecode: bool,
+    show_environment: bool,
+    optimize: bool,
+    no_filesystem: bool,
+    no_console: bool,
+    gc_stats: bool,
+    gc_stress: bool,
+    engine: EngineKind,
+    save_bytecode: Option<std::path::PathBuf>,
+}
+
+/* the engine executing programs. the repl keeps one for the whole session so globals persist */
+enum Engine {
+    Evaluator(Box<evaluator::Evaluator>),
+    Vm(Box<vm::Vm>),
+}
+
+impl Engine {
+    /* prints the error the program stops with, if any */
+    fn execute(&mut self, ast: &parser::Ast) -> Result<(), Error> {
+        match self {
+            Engine::Evaluator(evaluator) => report(evaluator.evaluate(ast)),
+            Engine::Vm(vm) => match vm::compile(ast) {
+                Ok(prototype) => report(vm.run(prototype)),
+                Err(error) => report(Err(error)),
+            },
+        }
+    }
+
+    fn interrupt_handle(&self) -> evaluator::InterruptHandle {
+        match self {
+            Engine::Evaluator(evaluator) => evaluator.interrupt_handle(),
+            Engine::Vm(vm) => vm.interrupt_handle(),
+        }
+    }
+
+    /* --gc-stats is rejected for the virtual machine, which has no stats to print */
+    fn print_gc_stats(&self) {
+        if let Engine::Evaluator(evaluator) = self {
+            let stats = evaluator.gc_stats();
+
+            println!(
+                "gc: {} collections, {} objects freed, {} bytes freed",
+                stats.collections, stats.objects_freed, stats.bytes_freed
+            );
+        }
+    }
+
+    fn print_environment(&self) {
+        println!();
+
+        match self {
+            Engine::Evaluator(evaluator) => println!("{:?}", evaluator.environment),
+            Engine::Vm(vm) => println!("{:?}", vm),
+        }
+    }
 }
 
 impl Interpreter {
@@ -62,33 +219,99 @@ impl Interpreter {
             mode,
             show_tokens: args.show_tokens,
             show_ast: args.show_ast,
+            show_bytecode: args.show_bytecode,
+            show_environment: args.show_environment,
+            optimize: args.optimize,
+            no_filesystem: args.no_filesystem,
+            no_console: args.no_console,
+            gc_stats: args.gc_stats,
+            gc_stress: args.gc_stress,
+            engine: args.engine,
+            save_bytecode: args.save_bytecode,
         }
     }
 
     pub fn interpret(&self) -> Result<(), Error> {
         match &self.mode {
-            Mode::Script { path } => self.interpret_file(&path),
-            Mode::Repl => Ok(self.interpret_repl()),
+            Mode::Script { path } => self.interpret_file(path),
+            Mode::Repl => {
+                self.interpret_repl();
+                Ok(())
+            }
         }
     }
 
     fn interpret_file(&self, path: &std::path::Path) -> Result<(), Error> {
-        let code = std::fs::read_to_string(path).unwrap();
-        let ast = self.lex_and_parse(&code).ok_or_else(|| Error::ParseError)?;
-        let mut evaluator = evaluator::Evaluator::new();
+        let bytes = std::fs::read(path).map_err(|error| {
+            println!("Error reading {}: {error}", path.display());
+            Error::Read
+        })?;
+
+        if vm::is_serialized(&bytes) {
+            return self.interpret_bytecode(&bytes);
+        }
+
+        let code = String::from_utf8(bytes).map_err(|_| {
+            println!("Error reading {}: not valid UTF-8", path.display());
+            Error::Read
+        })?;
+        let ast = self.lex_and_parse(&code).ok_or(Error::Parse)?;
+
+        if let Some(path) = &self.save_bytecode {
+            return save_bytecode(&ast, path);
+        }
 
-        if let Err(error) = evaluator.evaluate(&ast) {
+        let mut engine = self.engine();
+
+        let result = engine.execute(&ast);
+
+        if self.gc_stats {
+            engine.print_gc_stats();
+        }
+
+        if self.show_environment {
+            engine.print_environment();
+        }
+
+        result
+    }
+
+    /* saved bytecode runs on the virtual machine whichever engine was chosen */
+    fn interpret_bytecode(&self, mut bytes: &[u8]) -> Result<(), Error> {
+        let prototype = vm::load(&mut bytes).map_err(|error| {
             println!("error: {:?}", error);
+            Error::Load
+        })?;
+
+        if self.show_bytecode {
+            print!("{}", vm::disassemble(&prototype));
         }
 
-        println!();
-        println!("{:?}", evaluator.environment);
+        if self.gc_stats || self.gc_stress {
+            println!(
+                "warning: saved bytecode runs on the virtual machine, which has no garbage collector"
+            );
+        }
+
+        let mut machine = self.vm();
+        let result = report(machine.run(prototype));
+
+        if self.show_environment {
+            println!();
+            println!("{:?}", machine);
+        }
 
-        Ok(())
+        result
     }
 
     fn interpret_repl(&self) {
-        let mut evaluator = evaluator::Evaluator::new();
+        let mut engine = self.engine();
+
+        /* ctrl-c stops the input being evaluated rather than the whole repl */
+        let interrupt = engine.interrupt_handle();
+        if let Err(error) = ctrlc::set_handler(move || interrupt.interrupt()) {
+            println!("Error installing the interrupt handler: {error}");
+        }
 
         let stdin = io::stdin();
         let mut code = String::new();
@@ -97,7 +320,7 @@ impl Interpreter {
             code.clear();
 
             let ast = match stdin.read_line(&mut code) {
-                Ok(n) if n == 0 => return,
+                Ok(0) => return,
                 Err(error) => {
                     println!("Error reading input: {error}");
                     continue;
@@ -108,17 +331,44 @@ impl Interpreter {
                 },
             };
 
-            if let Err(error) = evaluator.evaluate(&ast) {
-                println!("error: {:?}", error);
+            /* the error has already been printed, and the repl carries on regardless */
+            let _ = engine.execute(&ast);
+
+            if self.gc_stats {
+                engine.print_gc_stats();
             }
 
-            println!();
-            println!("{:?}", evaluator.environment);
+            if self.show_environment {
+                engine.print_environment();
+            }
         }
     }
 
+    /* --gc-stress is rejected for the virtual machine, which has no garbage collector */
+    fn engine(&self) -> Engine {
+        match self.engine {
+            EngineKind::Evaluator => {
+                let mut evaluator = evaluator::Evaluator::new();
+                evaluator.set_filesystem_access(!self.no_filesystem);
+                evaluator.set_console_access(!self.no_console);
+                evaluator.set_gc_stress(self.gc_stress);
+
+                Engine::Evaluator(Box::new(evaluator))
+            }
+            EngineKind::Vm => Engine::Vm(Box::new(self.vm())),
+        }
+    }
+
+    fn vm(&self) -> vm::Vm {
+        let mut vm = vm::Vm::new();
+        vm.set_filesystem_access(!self.no_filesystem);
+        vm.set_console_access(!self.no_console);
+
+        vm
+    }
+
     fn lex_and_parse(&self, code: &str) -> Option<parser::Ast> {
-        let tokens = match lexer::tokenize(&code) {
+        let tokens = match lexer::tokenize(code) {
             Ok(tokens) => tokens,
             Err(tokens) => tokens,
         };
@@ -127,10 +377,10 @@ impl Interpreter {
             println!("{:#?}", tokens);
         }
 
-        let ast = match parser::Ast::new(tokens.into_iter()) {
+        let mut ast = match parser::Ast::new(tokens.into_iter()) {
             Ok(ast) => ast,
             Err(errors) => {
-                for context in errors.error_contexts(&code) {
+                for context in errors.error_contexts(code) {
                     println!("{context}");
                 }
 
@@ -138,10 +388,52 @@ impl Interpreter {
             }
         };
 
+        if let Err(errors) = ast.resolve() {
+            for error in errors {
+                println!("error: {:?}", error);
+            }
+
+            return None;
+        }
+
+        if self.optimize {
+            ast.optimize();
+        }
+
         if self.show_ast {
             println!("{}", ast);
         }
 
+        if self.show_bytecode {
+            match vm::compile(&ast) {
+                Ok(prototype) => print!("{}", vm::disassemble(&prototype)),
+                Err(error) => println!("error: {:?}", error),
+            }
+        }
+
         Some(ast)
     }
 }
+
+/* prints the error a program stopped with, if any */
+fn report(result: Result<(), impl std::fmt::Debug>) -> Result<(), Error> {
+    result.map_err(|error| {
+        println!("error: {:?}", error);
+        Error::Evaluate
+    })
+}
+
+fn save_bytecode(ast: &parser::Ast, path: &std::path::Path) -> Result<(), Error> {
+    let prototype = vm::compile(ast).map_err(|error| {
+        println!("error: {:?}", error);
+        Error::Save
+    })?;
+
+    let mut bytes = Vec::new();
+    vm::save(&prototype, &mut bytes).unwrap();
+
+    std::fs::write(path, bytes).map_err(|error| {
+        println!("Error writing {}: {error}", path.display());
+        Error::Save
+    })
+}
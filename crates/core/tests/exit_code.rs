@@ -0,0 +1,74 @@
+//! Runs the interpreter binary on small scripts and checks that it only exits successfully when
+//! the script runs to completion.
+
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+
+fn run_script(name: &str, code: impl AsRef<[u8]>) -> ExitStatus {
+    run_script_with(name, &[], code)
+}
+
+fn run_script_with(name: &str, arguments: &[&str], code: impl AsRef<[u8]>) -> ExitStatus {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("exit_code_{name}_{}.lox", std::process::id()));
+    std::fs::write(&path, code).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_core"))
+        .args(arguments)
+        .arg(&path)
+        .output()
+        .unwrap()
+        .status;
+
+    std::fs::remove_file(&path).unwrap();
+    status
+}
+
+#[test]
+fn test_success() {
+    assert!(run_script("success", "var a = 1;").success());
+}
+
+#[test]
+fn test_unreadable_script_fails() {
+    let status = Command::new(env!("CARGO_BIN_EXE_core"))
+        .arg(std::env::temp_dir().join("exit_code_missing.lox"))
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(1));
+
+    assert_eq!(run_script("invalid_utf8", b"print \xff;").code(), Some(1));
+}
+
+#[test]
+fn test_gc_flags_need_the_evaluator() {
+    for flag in ["--gc-stats", "--gc-stress"] {
+        let status = run_script_with("gc_flags", &["--engine", "vm", flag], "var a = 1;");
+        assert_eq!(status.code(), Some(2));
+    }
+}
+
+#[test]
+fn test_parse_error_fails() {
+    assert!(!run_script("parse_error", "var = ;").success());
+}
+
+#[test]
+fn test_runtime_error_fails() {
+    assert!(!run_script("runtime_error", "var a = undefinedVariable;").success());
+}
+
+/* runaway recursion must stop at the call depth limit rather than crash on a stack overflow */
+#[test]
+fn test_unbounded_recursion_fails() {
+    let status = run_script("recursion", "fun f() { return f(); } f();");
+    assert_eq!(status.code(), Some(1));
+
+    let status = run_script_with(
+        "vm_recursion",
+        &["--engine", "vm"],
+        "fun f() { return f(); } f();",
+    );
+    assert_eq!(status.code(), Some(1));
+}
@@ -30,10 +30,7 @@ where
         self.errors.iter().map(|error| error.kind.clone())
     }
 
-    pub fn error_contexts<'a>(
-        &self,
-        input: &'a str,
-    ) -> impl Iterator<Item = ErrorContext<ErrorKind>> {
+    pub fn error_contexts(&self, input: &str) -> impl Iterator<Item = ErrorContext<ErrorKind>> {
         get_error_contexts(input, self.errors.iter()).into_iter()
     }
 }
@@ -41,6 +38,8 @@ where
 pub struct ErrorRecorder<ErrorKind, I: Iterator<Item = lexer::Token>> {
     tokens: std::iter::Peekable<I>,
     original_length: usize,
+    /* the newlines filtered out of the tokens so far */
+    newlines: std::rc::Rc<std::cell::Cell<usize>>,
     errors: Vec<RecordedError<ErrorKind>>,
 }
 
@@ -56,16 +55,21 @@ impl<ErrorKind> ErrorRecorder<ErrorKind, DummyIterator> {
     pub fn new<I: ExactSizeIterator<Item = lexer::Token>>(
         tokens: I,
     ) -> ErrorRecorder<ErrorKind, impl Iterator<Item = lexer::Token>> {
+        let newlines = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = newlines.clone();
+
         ErrorRecorder {
             original_length: tokens.len(),
             tokens: tokens
-                .filter(|token| {
-                    !matches!(
-                        token,
-                        lexer::Token::FixedToken(lexer::tokens::FixedToken::Newline)
-                    )
+                .filter(move |token| match token {
+                    lexer::Token::FixedToken(lexer::tokens::FixedToken::Newline) => {
+                        counter.set(counter.get() + 1);
+                        false
+                    }
+                    _ => true,
                 })
                 .peekable(),
+            newlines,
             errors: Vec::new(),
         }
     }
@@ -87,6 +91,13 @@ where
         });
     }
 
+    /// The line, counting from 1, that the next token is on.
+    pub fn line(&mut self) -> usize {
+        /* peeking skips the newlines before the next token */
+        self.tokens.peek();
+        self.newlines.get() + 1
+    }
+
     pub fn errors(self) -> Errors<ErrorKind> {
         Errors::new(self.errors)
     }
@@ -107,7 +118,7 @@ pub struct ErrorContext<ErrorKind> {
     line: String,
 }
 
-impl<'a, ErrorKind: Clone + std::fmt::Debug> ErrorContext<ErrorKind> {
+impl<ErrorKind: Clone + std::fmt::Debug> ErrorContext<ErrorKind> {
     pub fn kind(&self) -> ErrorKind {
         self.partial.kind.clone()
     }
@@ -121,7 +132,7 @@ impl<'a, ErrorKind: Clone + std::fmt::Debug> ErrorContext<ErrorKind> {
     }
 }
 
-impl<'a, ErrorKind: Clone + std::fmt::Debug> std::fmt::Display for ErrorContext<ErrorKind> {
+impl<ErrorKind: Clone + std::fmt::Debug> std::fmt::Display for ErrorContext<ErrorKind> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{:?}, {}", self.kind(), self.column())?;
 
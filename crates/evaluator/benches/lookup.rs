@@ -0,0 +1,71 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+
+use evaluator::Evaluator;
+
+fn parse(code: &str) -> parser::Ast {
+    let tokens = lexer::tokenize(code).unwrap();
+    let mut ast = parser::Ast::new(tokens.into_iter()).unwrap();
+    ast.resolve().unwrap();
+
+    ast
+}
+
+fn run(criterion: &mut Criterion, name: &str, code: &str) {
+    let ast = parse(code);
+
+    criterion.bench_function(name, |bencher| {
+        bencher.iter(|| Evaluator::new().evaluate(&ast).unwrap())
+    });
+}
+
+fn fib(criterion: &mut Criterion) {
+    run(
+        criterion,
+        "fib",
+        "fun fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); } fib(20);",
+    );
+}
+
+fn nested_loops(criterion: &mut Criterion) {
+    run(
+        criterion,
+        "nested loops",
+        "{
+            var sum = 0;
+            for (var i = 0; i < 100; i = i + 1) {
+                for (var j = 0; j < 100; j = j + 1) {
+                    sum = sum + i * j;
+                }
+            }
+        }",
+    );
+}
+
+/* globals are looked up by name, unlike the locals above */
+fn global_loops(criterion: &mut Criterion) {
+    run(
+        criterion,
+        "global loops",
+        "var sum = 0;
+        var step = 3;
+        for (var i = 0; i < 10000; i = i + 1) {
+            sum = sum + step;
+            step = sum % 7;
+        }",
+    );
+}
+
+fn string_constants(criterion: &mut Criterion) {
+    run(
+        criterion,
+        "string constants",
+        "var count = 0;
+        for (var i = 0; i < 10000; i = i + 1) {
+            var greeting = \"hello\";
+            count = count + len(greeting);
+        }",
+    );
+}
+
+criterion_group!(benches, fib, nested_loops, global_loops, string_constants);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+/// Where the `time` and `clock` natives read the current time from, as a duration since the
+/// unix epoch.
+pub trait TimeSource {
+    fn now(&self) -> Duration;
+}
+
+impl<F: Fn() -> Duration> TimeSource for F {
+    fn now(&self) -> Duration {
+        self()
+    }
+}
+
+/// The wall clock, which is the time source unless the host says otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a host can keep one to
+/// advance the clock the interpreter reads from.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new(now: Duration) -> Self {
+        ManualClock {
+            now: Rc::new(Cell::new(now)),
+        }
+    }
+
+    pub fn set(&self, now: Duration) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+pub struct Clock {
+    source: Box<dyn TimeSource>,
+}
+
+impl Clock {
+    pub fn new(source: Box<dyn TimeSource>) -> Self {
+        Clock { source }
+    }
+
+    pub fn now(&self) -> Duration {
+        self.source.now()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new(Box::new(SystemClock))
+    }
+}
+
+impl std::fmt::Debug for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Clock").finish_non_exhaustive()
+    }
+}
@@ -1,100 +1,438 @@
-use crate::evaluator::{Callable, EvaluatorResult, RuntimeError, Value};
+use crate::evaluator::{Arity, Context, EvaluatorResult, NativeFunction, RuntimeError, Value};
+use crate::heap::{GcStats, Heap};
+use crate::interrupt::InterruptHandle;
+use crate::limits::{Limits, Usage};
+use crate::native::{self, Host, Natives};
+use crate::system::System;
 
-use std::collections::HashMap;
-use std::time::SystemTime;
+use lexer::Symbol;
+use parser::grammar::Local;
+
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::time::Instant;
 
 #[derive(Debug)]
 pub struct Environment {
-    stack: Vec<Frame>,
+    globals: RefCell<HashMap<Symbol, Value>>,
+    /* the globals that scripts may read but not assign to, such as PI */
+    constants: HashSet<Symbol>,
+    stack: Vec<Rc<RefCell<Frame>>>,
+    /* the scopes of the functions that are waiting on a call, innermost last */
+    callers: Vec<Scope>,
+    system: System,
+    limits: Limits,
+    usage: Usage,
+    interrupt: InterruptHandle,
+    heap: Heap,
+}
+
+/// The chain of frames visible at some point of execution. Frames are shared, so a function
+/// that captures a scope sees later assignments to the variables in it.
+#[derive(Debug, Clone)]
+pub struct Scope {
+    stack: Vec<Rc<RefCell<Frame>>>,
 }
 
+/* a frame holds the locals of one scope, in the order the resolver assigned their slots */
 #[derive(Debug)]
-struct Frame {
-    variables: HashMap<String, Value>,
+pub(crate) struct Frame {
+    pub(crate) values: Vec<Value>,
 }
 
 impl Environment {
     pub fn new() -> Self {
-        let mut environment = Self { stack: Vec::new() };
-        environment.push(); /* global frame */
-
-        environment.declare_variable(
-            "time",
-            Value::Callable(Callable::new(0, |_| {
-                Value::Numeric(
-                    SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as f64,
-                )
-            })),
-        ).unwrap();
+        let mut environment = Self {
+            globals: RefCell::new(HashMap::new()),
+            constants: HashSet::new(),
+            stack: Vec::new(),
+            callers: Vec::new(),
+            system: System::new(),
+            limits: Limits::default(),
+            usage: Usage::default(),
+            interrupt: InterruptHandle::default(),
+            heap: Heap::new(),
+        };
+
+        native::standard_library(&mut environment);
 
         environment
     }
 
-    pub fn push(&mut self) {
-        self.stack.push(Frame::new())
+    pub fn system(&self) -> &System {
+        &self.system
+    }
+
+    pub fn system_mut(&mut self) -> &mut System {
+        &mut self.system
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Fails if the evaluation has been interrupted since it started, or since the last check.
+    pub fn check_interrupt(&self) -> EvaluatorResult<()> {
+        match self.interrupt.take() {
+            true => Err(RuntimeError::Interrupted),
+            false => Ok(()),
+        }
+    }
+
+    /// Hands a new evaluation the full budget of every limit.
+    pub fn start_execution(&mut self) {
+        self.interrupt.clear();
+        self.usage.steps = 0;
+        self.usage.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.usage.memory = match self.limits.max_memory {
+            Some(_) => self.memory_usage(),
+            None => 0,
+        };
+    }
+
+    /// Accounts for the execution of one statement.
+    pub fn step(&mut self) -> EvaluatorResult<()> {
+        self.usage.steps += 1;
+
+        if self
+            .limits
+            .max_steps
+            .is_some_and(|max_steps| self.usage.steps > max_steps)
+        {
+            return Err(RuntimeError::StepLimitExceeded);
+        }
+
+        if self
+            .usage
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(RuntimeError::Timeout);
+        }
+
+        Ok(())
+    }
+
+    /// Accounts for `bytes` of newly allocated memory.
+    pub fn allocate(&mut self, bytes: usize) -> EvaluatorResult<()> {
+        let Some(max_memory) = self.limits.max_memory else {
+            return Ok(());
+        };
+
+        self.usage.memory += bytes;
+
+        if self.usage.memory > max_memory {
+            self.usage.memory = self.memory_usage();
+
+            if self.usage.memory > max_memory {
+                return Err(RuntimeError::MemoryLimitExceeded);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hands the lists, maps and closures in a value that was created by the evaluator, or passed
+    /// to it, to the garbage collector, which may run before this returns.
+    pub fn track(&mut self, value: &Value) {
+        self.heap.track(value)
+    }
+
+    pub fn collect_garbage(&mut self) {
+        self.heap.collect()
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap.stats()
+    }
+
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.heap.set_stress(stress)
+    }
+
+    /* the approximate size of everything a script can still reach */
+    fn memory_usage(&self) -> usize {
+        let mut walk = MemoryWalk::default();
+
+        for value in self.globals.borrow().values() {
+            walk.value(value);
+        }
+
+        for scope in self.callers.iter().chain([&self.scope()]) {
+            walk.scope(scope);
+        }
+
+        walk.bytes
+    }
+
+    pub fn print(&mut self, value: &Value) -> EvaluatorResult<()> {
+        writeln!(self.system.output(), "{value}").map_err(RuntimeError::Io)
+    }
+
+    /// Opens a new innermost scope, which stays open for as long as the returned guard lives.
+    pub fn enter_scope(&mut self) -> ScopeGuard<'_> {
+        let depth = self.stack.len();
+        self.push();
+
+        ScopeGuard {
+            environment: self,
+            returns_to_caller: false,
+            depth,
+        }
+    }
+
+    /// Switches to a function's captured scope and opens a frame on top of it for the call. The
+    /// caller's scope is restored when the returned guard is dropped. Fails if the call would nest
+    /// deeper than the limits allow.
+    pub fn enter_function(&mut self, scope: Scope) -> EvaluatorResult<ScopeGuard<'_>> {
+        if self
+            .limits
+            .max_call_depth
+            .is_some_and(|max_call_depth| self.callers.len() >= max_call_depth)
+        {
+            return Err(RuntimeError::CallDepthExceeded);
+        }
+
+        let caller = self.replace_scope(scope);
+        self.callers.push(caller);
+        self.push();
+
+        Ok(ScopeGuard {
+            environment: self,
+            returns_to_caller: true,
+            depth: 0,
+        })
+    }
+
+    /// The number of local scopes currently open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn push(&mut self) {
+        self.stack.push(Rc::new(RefCell::new(Frame::new())))
+    }
+
+    /// Declares a variable in the innermost scope, or a global when no scope is open.
+    pub fn declare_variable(&mut self, identifier: Symbol, value: Value) -> EvaluatorResult<()> {
+        match self.stack.last() {
+            Some(frame) => {
+                frame.borrow_mut().values.push(value);
+                Ok(())
+            }
+            None => {
+                let mut globals = self.globals.borrow_mut();
+
+                if globals.contains_key(&identifier) {
+                    return Err(RuntimeError::VariableRedefinition);
+                }
+
+                globals.insert(identifier, value);
+                Ok(())
+            }
+        }
     }
 
-    pub fn pop(&mut self) {
-        self.stack.pop();
+    /// Declares or overwrites a global, regardless of which scopes are open.
+    pub fn define_global(&mut self, identifier: Symbol, value: Value) {
+        self.track(&value);
+        self.globals.borrow_mut().insert(identifier, value);
     }
 
-    pub fn declare_variable(&mut self, identifier: &str, value: Value) -> EvaluatorResult<()> {
-        let len = self.stack.len();
+    /// Defines a global that scripts can't assign to. Assigning to it fails with
+    /// `RuntimeError::ConstantAssignment`, though the host can still redefine it.
+    pub fn define_constant(&mut self, identifier: Symbol, value: Value) {
+        self.define_global(identifier, value);
+        self.constants.insert(identifier);
+    }
 
-        /* this is safe because the vector will never be empty */
-        unsafe {
-            self.stack
-                .get_unchecked_mut(len - 1)
-                .declare_variable(identifier, value)
+    /// Looks up the local at `local`, or the global named `identifier` if the resolver found no
+    /// local declaration for it.
+    pub fn lookup_variable(
+        &self,
+        identifier: Symbol,
+        local: Option<Local>,
+    ) -> Option<Ref<'_, Value>> {
+        match local {
+            Some(local) => Ref::filter_map(self.frame(local).borrow(), |frame| {
+                frame.values.get(local.slot)
+            })
+            .ok(),
+            None => Ref::filter_map(self.globals.borrow(), |globals| globals.get(&identifier)).ok(),
         }
     }
 
-    pub fn lookup_variable(&self, identifier: &str) -> Option<Value> {
-        for frame in self.stack.iter().rev() {
-            if let Some(value) = frame.lookup_variable(identifier) {
-                return Some(value);
+    pub fn assign_variable(
+        &mut self,
+        identifier: Symbol,
+        local: Option<Local>,
+        value: Value,
+    ) -> EvaluatorResult<Value> {
+        if local.is_none() && self.constants.contains(&identifier) {
+            return Err(RuntimeError::ConstantAssignment);
+        }
+
+        let value_ref = match local {
+            Some(local) => RefMut::filter_map(self.frame(local).borrow_mut(), |frame| {
+                frame.values.get_mut(local.slot)
+            })
+            .ok(),
+            None => RefMut::filter_map(self.globals.borrow_mut(), |globals| {
+                globals.get_mut(&identifier)
+            })
+            .ok(),
+        };
+
+        match value_ref {
+            Some(mut value_ref) => {
+                *value_ref = value.clone();
+                Ok(value)
             }
+            None => Err(RuntimeError::VariableDoesNotExist),
+        }
+    }
+
+    fn frame(&self, local: Local) -> &Rc<RefCell<Frame>> {
+        &self.stack[self.stack.len() - 1 - local.depth]
+    }
+
+    pub fn scope(&self) -> Scope {
+        Scope {
+            stack: self.stack.clone(),
         }
+    }
 
-        None
+    /* makes `scope` the current scope, returning the one it replaces */
+    fn replace_scope(&mut self, scope: Scope) -> Scope {
+        Scope {
+            stack: std::mem::replace(&mut self.stack, scope.stack),
+        }
     }
+}
+
+/// Closes the scope it was created for when dropped, so that the frame is popped on every way out
+/// of a block, loop or call, including errors and returns.
+pub struct ScopeGuard<'a> {
+    environment: &'a mut Environment,
+    returns_to_caller: bool,
+    depth: usize,
+}
 
-    pub fn assign_variable(&mut self, identifier: &str, value: Value) -> EvaluatorResult<Value> {
-        for frame in self.stack.iter_mut().rev() {
-            if let Some(value_ref) = frame.lookup_variable_mut(identifier) {
-                *value_ref = value;
-                return Ok(value_ref.clone())
+impl Deref for ScopeGuard<'_> {
+    type Target = Environment;
+
+    fn deref(&self) -> &Environment {
+        self.environment
+    }
+}
+
+impl DerefMut for ScopeGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Environment {
+        self.environment
+    }
+}
+
+impl Drop for ScopeGuard<'_> {
+    fn drop(&mut self) {
+        match self.returns_to_caller {
+            true => {
+                let caller = self.environment.callers.pop().unwrap();
+                self.environment.replace_scope(caller);
             }
+            /* truncating rather than popping also closes any scope leaked inside this one */
+            false => self.environment.stack.truncate(self.depth),
         }
+    }
+}
 
-        Err(RuntimeError::VariableDoesNotExist)
+impl Scope {
+    pub(crate) fn frames(&self) -> &[Rc<RefCell<Frame>>] {
+        &self.stack
     }
 }
 
 impl Frame {
     pub fn new() -> Self {
-        Self {
-            variables: HashMap::new(),
-        }
+        Self { values: Vec::new() }
     }
+}
 
-    pub fn declare_variable(&mut self, identifier: &str, value: Value) -> EvaluatorResult<()> {
-        if self.variables.contains_key(identifier) {
-            return Err(RuntimeError::VariableRedefinition);
-        }
+/* the standard library's names are known not to clash */
+impl Natives<Value> for Environment {
+    fn native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut dyn Host, &[Value]) -> EvaluatorResult<Value> + 'static,
+    ) {
+        let native = NativeFunction::new(name, arity, move |context: &mut Context, arguments| {
+            function(context, arguments)
+        });
 
-        self.variables.insert(String::from(identifier), value);
-        Ok(())
+        self.declare_variable(Symbol::intern(name), Value::NativeFunction(native))
+            .unwrap();
     }
 
-    pub fn lookup_variable(&self, identifier: &str) -> Option<Value> {
-        self.variables.get(identifier).map(|value| value.clone())
+    fn constant(&mut self, name: &str, value: Value) {
+        self.define_constant(Symbol::intern(name), value);
     }
+}
 
-    pub fn lookup_variable_mut(&mut self, identifier: &str) -> Option<&mut Value> {
-        self.variables.get_mut(identifier)
+/* visits every value once, however many references to it there are, so that shared and cyclic
+ * lists, maps and frames are counted once */
+#[derive(Default)]
+struct MemoryWalk {
+    visited: HashSet<*const ()>,
+    bytes: usize,
+}
+
+impl MemoryWalk {
+    fn first_visit<T>(&mut self, shared: &Rc<T>) -> bool {
+        self.visited.insert(Rc::as_ptr(shared) as *const ())
+    }
+
+    fn scope(&mut self, scope: &Scope) {
+        for frame in &scope.stack {
+            if self.first_visit(frame) {
+                for value in &frame.borrow().values {
+                    self.value(value);
+                }
+            }
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        self.bytes += std::mem::size_of::<Value>();
+
+        match value {
+            Value::String_(string) => self.bytes += string.len(),
+            Value::List(list) => {
+                if self.first_visit(list) {
+                    for element in list.borrow().iter() {
+                        self.value(element);
+                    }
+                }
+            }
+            Value::Map(map) => {
+                if self.first_visit(map) {
+                    for (key, value) in map.borrow().iter() {
+                        self.bytes += key.allocation();
+                        self.value(value);
+                    }
+                }
+            }
+            Value::Function(closure) => {
+                if self.first_visit(closure) {
+                    self.scope(closure.scope());
+                }
+            }
+            Value::Numeric(_) | Value::Boolean(_) | Value::NativeFunction(_) | Value::Nil => (),
+        }
     }
 }
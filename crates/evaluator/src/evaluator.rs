@@ -2,17 +2,52 @@ mod declaration;
 mod expression;
 mod statement;
 
+use lexer::Symbol;
 use parser::Ast;
 
-use crate::environment::Environment;
+use indexmap::IndexMap;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::clock::TimeSource;
+use crate::environment::{Environment, Scope};
+use crate::heap::GcStats;
+use crate::interrupt::InterruptHandle;
+use crate::limits::Limits;
+use crate::native::NativeValue;
+use crate::system::System;
 
 #[derive(Debug)]
 pub enum RuntimeError {
     VariableRedefinition,
     VariableDoesNotExist,
+    /* the ast was evaluated without being resolved, so its locals can't be found */
+    UnresolvedAst,
+    ConstantAssignment,
     NotCallable,
     WrongNumberOfArguments,
+    UndefinedProperty,
+    NotIndexable,
+    InvalidIndex,
+    IndexOutOfBounds,
+    UnhashableKey,
+    UndefinedKey,
     TypeError,
+    InvalidArgument,
+    FilesystemAccessDenied,
+    ConsoleAccessDenied,
+    DivisionByZero,
+    StepLimitExceeded,
+    Timeout,
+    CallDepthExceeded,
+    /* only the virtual machine, whose frames live on the heap, can run out of them */
+    StackOverflow,
+    /* likewise, for a loaded program whose instructions misuse the stack or their operands */
+    MalformedProgram,
+    MemoryLimitExceeded,
+    Interrupted,
+    Io(std::io::Error),
 }
 
 pub type EvaluatorResult<T> = Result<T, RuntimeError>;
@@ -20,66 +55,514 @@ pub type EvaluatorResult<T> = Result<T, RuntimeError>;
 #[derive(Debug, Clone)]
 pub enum Value {
     Numeric(f64),
-    String_(String),
+    String_(Rc<str>),
+    Boolean(bool),
+    NativeFunction(NativeFunction),
+    Function(Rc<Closure>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<IndexMap<MapKey, Value>>>),
+    Nil,
+}
+
+/// The subset of values that may key a map. Entries keep their insertion order.
+#[derive(Debug, Clone)]
+pub enum MapKey {
+    Numeric(f64),
+    String_(Rc<str>),
     Boolean(bool),
-    Callable(Callable),
     Nil,
 }
 
 impl Value {
+    pub fn list(elements: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn map(entries: IndexMap<MapKey, Value>) -> Self {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    /* the heap memory the value owns directly, not counting what its elements own */
+    fn allocation(&self) -> usize {
+        match self {
+            Value::String_(string) => string.len(),
+            Value::List(list) => list.borrow().len() * std::mem::size_of::<Value>(),
+            Value::Map(map) => {
+                map.borrow().len() * (std::mem::size_of::<MapKey>() + std::mem::size_of::<Value>())
+            }
+            _ => 0,
+        }
+    }
+
+    /// Every condition follows lox's rule: `nil` and `false` are falsey, everything else is truthy.
     fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Boolean(false) | Value::Nil)
+    }
+}
+
+/* conversions from rust values, so that hosts can pass them to scripts */
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Nil, Into::into)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Numeric(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String_(Rc::from(value))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String_(Rc::from(value))
+    }
+}
+
+impl From<Rc<str>> for Value {
+    fn from(value: Rc<str>) -> Self {
+        Value::String_(value)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(elements: Vec<T>) -> Self {
+        Value::list(elements.into_iter().map(Into::into).collect())
+    }
+}
+
+/* and back again, failing with a type error when the value has another type */
+
+impl TryFrom<Value> for f64 {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Numeric(value) => Ok(value),
+            _ => Err(RuntimeError::TypeError),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Boolean(value) => Ok(value),
+            _ => Err(RuntimeError::TypeError),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::String_(value) => Ok(String::from(&*value)),
+            _ => Err(RuntimeError::TypeError),
+        }
+    }
+}
+
+impl<T: TryFrom<Value, Error = RuntimeError>> TryFrom<Value> for Vec<T> {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::List(list) => list.borrow().iter().cloned().map(T::try_from).collect(),
+            _ => Err(RuntimeError::TypeError),
+        }
+    }
+}
+
+impl NativeValue for Value {
+    type Key = MapKey;
+
+    fn nil() -> Self {
+        Value::Nil
+    }
+
+    fn list(elements: Vec<Self>) -> Self {
+        Value::list(elements)
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Numeric(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String_(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn as_list(&self) -> Option<&RefCell<Vec<Self>>> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    fn as_map(&self) -> Option<&RefCell<IndexMap<MapKey, Self>>> {
         match self {
-            Value::Boolean(bool) if !bool => false,
-            Value::Nil => false,
-            _ => true,
+            Value::Map(map) => Some(map),
+            _ => None,
         }
     }
 }
 
-impl From<Option<Self>> for Value {
-    fn from(value: Option<Self>) -> Self {
-        value.unwrap_or(Value::Nil)
+/* values of different types are never equal. lists, maps and functions are equal only to
+ * themselves, while everything else compares by value */
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Numeric(left), Value::Numeric(right)) => left == right,
+            (Value::String_(left), Value::String_(right)) => left == right,
+            (Value::Boolean(left), Value::Boolean(right)) => left == right,
+            (Value::NativeFunction(left), Value::NativeFunction(right)) => {
+                Rc::ptr_eq(&left.function, &right.function)
+            }
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::List(left), Value::List(right)) => Rc::ptr_eq(left, right),
+            (Value::Map(left), Value::Map(right)) => Rc::ptr_eq(left, right),
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
     }
 }
 
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_value(f, self, &mut Vec::new())
+    }
+}
+
+/* `printing` holds the lists and maps that are being printed, so that a collection containing
+ * itself prints `[...]` or `{...}` where it repeats instead of recursing forever */
+fn write_value(
+    f: &mut std::fmt::Formatter<'_>,
+    value: &Value,
+    printing: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match value {
+        Value::Numeric(value) => write!(f, "{value}"),
+        Value::String_(value) => write!(f, "{value}"),
+        Value::Boolean(value) => write!(f, "{value}"),
+        Value::NativeFunction(native) => write!(f, "<native fn {}>", native.name()),
+        Value::Function(closure) => match &closure.function.name {
+            Some(name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<fn>"),
+        },
+        Value::List(list) => write_collection(
+            f,
+            Rc::as_ptr(list) as _,
+            "[...]",
+            printing,
+            |f, printing| {
+                write!(f, "[")?;
+
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write_nested(f, element, printing)?;
+                }
+
+                write!(f, "]")
+            },
+        ),
+        Value::Map(map) => {
+            write_collection(f, Rc::as_ptr(map) as _, "{...}", printing, |f, printing| {
+                write!(f, "{{")?;
+
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write_nested(f, &Value::from(key.clone()), printing)?;
+                    write!(f, ": ")?;
+                    write_nested(f, value, printing)?;
+                }
+
+                write!(f, "}}")
+            })
+        }
+        Value::Nil => write!(f, "nil"),
+    }
+}
+
+fn write_collection(
+    f: &mut std::fmt::Formatter<'_>,
+    address: *const (),
+    repeated: &str,
+    printing: &mut Vec<*const ()>,
+    write: impl FnOnce(&mut std::fmt::Formatter<'_>, &mut Vec<*const ()>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    if printing.contains(&address) {
+        return write!(f, "{repeated}");
+    }
+
+    printing.push(address);
+    write(f, printing)?;
+    printing.pop();
+
+    Ok(())
+}
+
+/* strings inside collections are quoted so that `["a, b"]` and `["a", "b"]` print differently */
+fn write_nested(
+    f: &mut std::fmt::Formatter<'_>,
+    value: &Value,
+    printing: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match value {
+        Value::String_(value) => write!(f, "{value:?}"),
+        _ => write_value(f, value, printing),
+    }
+}
+
+impl MapKey {
+    pub fn allocation(&self) -> usize {
+        match self {
+            MapKey::String_(string) => std::mem::size_of::<MapKey>() + string.len(),
+            _ => std::mem::size_of::<MapKey>(),
+        }
+    }
+}
+
+impl TryFrom<Value> for MapKey {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            /* -0 and 0 compare equal, so they must also hash equally */
+            Value::Numeric(0.0) => Ok(MapKey::Numeric(0.0)),
+            Value::Numeric(value) => Ok(MapKey::Numeric(value)),
+            Value::String_(value) => Ok(MapKey::String_(value)),
+            Value::Boolean(value) => Ok(MapKey::Boolean(value)),
+            Value::Nil => Ok(MapKey::Nil),
+            _ => Err(RuntimeError::UnhashableKey),
+        }
+    }
+}
+
+impl From<MapKey> for Value {
+    fn from(key: MapKey) -> Self {
+        match key {
+            MapKey::Numeric(value) => Value::Numeric(value),
+            MapKey::String_(value) => Value::String_(value),
+            MapKey::Boolean(value) => Value::Boolean(value),
+            MapKey::Nil => Value::Nil,
+        }
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MapKey::Numeric(left), MapKey::Numeric(right)) => left.to_bits() == right.to_bits(),
+            (MapKey::String_(left), MapKey::String_(right)) => left == right,
+            (MapKey::Boolean(left), MapKey::Boolean(right)) => left == right,
+            (MapKey::Nil, MapKey::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MapKey {}
+
+impl std::hash::Hash for MapKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
         match self {
-            Value::Numeric(value) => write!(f, "{value}"),
-            Value::String_(value) => write!(f, "{value}"),
-            Value::Boolean(value) => write!(f, "{value}"),
-            Value::Callable(_) => write!(f, "<callable>"),
-            Value::Nil => write!(f, "nil"),
+            MapKey::Numeric(value) => value.to_bits().hash(state),
+            MapKey::String_(value) => value.hash(state),
+            MapKey::Boolean(value) => value.hash(state),
+            MapKey::Nil => (),
         }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct Callable {
-    arity: usize,
-    function: fn(&Vec<Value>) -> Value,
+/// The signature of a function implemented in Rust. Natives may capture state of their own, and
+/// reach back into the interpreter through the context.
+pub type NativeFn = dyn Fn(&mut Context, &[Value]) -> EvaluatorResult<Value>;
+
+/// A function implemented by the host rather than in Lox.
+#[derive(Clone)]
+pub struct NativeFunction {
+    name: String,
+    arity: Arity,
+    function: Rc<NativeFn>,
 }
 
-impl Callable {
-    pub fn new(arity: usize, function: fn(&Vec<Value>) -> Value) -> Self {
-        Callable { arity, function }
+/// The number of arguments a native function accepts.
+#[derive(Debug, Clone, Copy)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Context, &[Value]) -> EvaluatorResult<Value> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: String::from(name),
+            arity,
+            function: Rc::new(function),
+        }
     }
 
-    pub fn arity(&self) -> usize {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> Arity {
         self.arity
     }
 
-    pub fn call(&self, arguments: &Vec<Value>) -> Value {
-        (self.function)(arguments)
+    pub fn call(&self, context: &mut Context, arguments: &[Value]) -> EvaluatorResult<Value> {
+        (self.function)(context, arguments)
     }
 }
 
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exactly(arity) => count == arity,
+            Arity::AtLeast(minimum) => count >= minimum,
+        }
+    }
+}
+
+/// The interpreter as seen from inside a native function.
+pub struct Context<'a> {
+    environment: &'a mut Environment,
+}
+
+impl<'a> Context<'a> {
+    fn new(environment: &'a mut Environment) -> Self {
+        Context { environment }
+    }
+
+    /// Calls a Lox or native function value, such as a callback passed to the native.
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> EvaluatorResult<Value> {
+        expression::call(callee, arguments, self.environment)
+    }
+
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.environment
+            .lookup_variable(Symbol::get(name)?, None)
+            .map(|value| value.clone())
+    }
+
+    /// The current time according to the evaluator's time source, as a duration since the unix
+    /// epoch.
+    pub fn now(&self) -> std::time::Duration {
+        self.environment.system().now()
+    }
+
+    /// Counts memory a native allocates against the evaluator's memory limit. Natives that grow
+    /// a value passed to them should call this, since only the values they return are counted
+    /// for them.
+    pub fn allocate(&mut self, bytes: usize) -> EvaluatorResult<()> {
+        self.environment.allocate(bytes)
+    }
+
+    /// Whether natives may touch the filesystem, see `Evaluator::set_filesystem_access`.
+    pub fn filesystem_access(&self) -> bool {
+        self.environment.system().filesystem_access()
+    }
+
+    /// The streams, clock and capabilities the evaluator was set up with.
+    pub fn system(&mut self) -> &mut System {
+        self.environment.system_mut()
+    }
+}
+
+/// A function written in Lox, together with the scope it was defined in.
+pub struct Closure {
+    function: Rc<parser::grammar::Function>,
+    scope: Scope,
+}
+
+impl Closure {
+    pub fn new(function: Rc<parser::grammar::Function>, scope: Scope) -> Self {
+        Closure { function, scope }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.function.parameters.len()
+    }
+
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+}
+
+/* the captured scope usually contains the closure itself, so it is left out */
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Closure")
+            .field("name", &self.function.name)
+            .field("parameters", &self.function.parameters)
+            .finish_non_exhaustive()
+    }
+}
+
+/// How a statement finished executing: either by falling through to the next one, or by
+/// returning from the enclosing function.
+#[derive(Debug)]
+pub enum Completion {
+    Normal,
+    Return(Value),
+}
+
 trait EvaluateValue {
     fn evaluate(&self, environment: &mut Environment) -> Result<Value, RuntimeError>;
 }
 
 trait Evaluate {
-    fn evaluate(&self, environment: &mut Environment) -> Result<(), RuntimeError>;
+    fn evaluate(&self, environment: &mut Environment) -> Result<Completion, RuntimeError>;
 }
 
 pub struct Evaluator {
@@ -93,11 +576,120 @@ impl Evaluator {
         }
     }
 
+    /// Runs a program, which must have been resolved with `Ast::resolve`. An unresolved one fails
+    /// with `UnresolvedAst` before anything runs.
     pub fn evaluate(&mut self, ast: &Ast) -> Result<(), RuntimeError> {
-        for declaration in &ast.program.declarations {
-            declaration.evaluate(&mut self.environment)?;
+        if !ast.is_resolved() {
+            return Err(RuntimeError::UnresolvedAst);
+        }
+
+        self.environment.start_execution();
+        ast.program.evaluate(&mut self.environment).map(|_| ())
+    }
+
+    /// Redirects the output of `print` statements, which goes to stdout by default.
+    pub fn set_output(&mut self, sink: impl std::io::Write + 'static) {
+        self.environment.system_mut().set_output(sink)
+    }
+
+    /// Redirects the output of the `eprint` native, which goes to stderr by default.
+    pub fn set_error_output(&mut self, sink: impl std::io::Write + 'static) {
+        self.environment.system_mut().set_error_output(sink)
+    }
+
+    /// Replaces where the `readLine` native reads from, which is stdin by default.
+    pub fn set_input(&mut self, source: impl std::io::BufRead + 'static) {
+        self.environment.system_mut().set_input(source)
+    }
+
+    /// Bounds the work of every later evaluation, which fails with `StepLimitExceeded`,
+    /// `Timeout`, `CallDepthExceeded` or `MemoryLimitExceeded` once it goes over a limit.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.environment.set_limits(limits)
+    }
+
+    /// A handle that stops whichever evaluation is running when it is used. Interrupts that
+    /// arrive while the evaluator is idle are ignored.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.environment.interrupt_handle()
+    }
+
+    /// Replaces the time source of the `time` and `clock` natives, which is the system clock by
+    /// default. Tests can pass a `ManualClock` to make the output of a script reproducible.
+    pub fn set_time_source(&mut self, source: impl TimeSource + 'static) {
+        self.environment.system_mut().set_time_source(source)
+    }
+
+    /// Allows or denies the filesystem natives, such as `readFile`. Access is allowed by
+    /// default; a sandboxed embedding can deny it, making those natives fail with
+    /// `RuntimeError::FilesystemAccessDenied`.
+    pub fn set_filesystem_access(&mut self, allowed: bool) {
+        self.environment.system_mut().set_filesystem_access(allowed)
+    }
+
+    /// Allows or denies the console natives, `readLine` and `eprint`. Access is allowed by
+    /// default; denying it makes them fail with `RuntimeError::ConsoleAccessDenied`.
+    pub fn set_console_access(&mut self, allowed: bool) {
+        self.environment.system_mut().set_console_access(allowed)
+    }
+
+    /// Calls a function value, as a call expression in a script would.
+    pub fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> EvaluatorResult<Value> {
+        self.environment.start_execution();
+
+        for argument in &arguments {
+            self.environment.track(argument);
         }
 
-        Ok(())
+        expression::call(callee, arguments, &mut self.environment)
+    }
+
+    /// Frees the lists, maps and closures that scripts can no longer reach but that reference
+    /// counting can't free, because they refer to each other. The evaluator collects on its own
+    /// as scripts allocate, so this is only needed to free them sooner.
+    pub fn collect_garbage(&mut self) {
+        self.environment.collect_garbage()
+    }
+
+    /// How many times the garbage collector has run, and what it has freed.
+    pub fn gc_stats(&self) -> GcStats {
+        self.environment.gc_stats()
+    }
+
+    /// Makes the garbage collector run every time a list, map, closure or scope is created,
+    /// which is slow but shows up any value the evaluator uses without keeping it reachable.
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.environment.set_gc_stress(stress)
+    }
+
+    /* a name that was never interned can't have been declared */
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.environment
+            .lookup_variable(Symbol::get(name)?, None)
+            .map(|value| value.clone())
+    }
+
+    /// Sets a global variable, declaring it if it doesn't exist yet.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.environment.define_global(Symbol::intern(name), value)
+    }
+
+    /// Defines a global native function, which scripts evaluated afterwards can call.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Context, &[Value]) -> EvaluatorResult<Value> + 'static,
+    ) -> EvaluatorResult<()> {
+        let native = NativeFunction::new(name, arity, function);
+
+        self.environment
+            .declare_variable(Symbol::intern(name), Value::NativeFunction(native))
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -2,30 +2,45 @@ use crate::evaluator::*;
 use parser::grammar::*;
 
 impl Evaluate for Program {
-    fn evaluate(&self, environment: &mut Environment) -> Result<(), RuntimeError> {
-        todo!()
+    fn evaluate(&self, environment: &mut Environment) -> Result<Completion, RuntimeError> {
+        /* the resolver rejects a return outside of any function */
+        for declaration in &self.declarations {
+            declaration.evaluate(environment)?;
+        }
+
+        Ok(Completion::Normal)
     }
 }
 
 impl Evaluate for Declaration {
-    fn evaluate(&self, environment: &mut Environment) -> Result<(), RuntimeError> {
-        match self {
-            Declaration::Statement(statement) => statement.evaluate(environment),
-            Declaration::VariableDeclaration(variable_declaration) => {
+    fn evaluate(&self, environment: &mut Environment) -> Result<Completion, RuntimeError> {
+        match &self.kind {
+            DeclarationKind::Statement(statement) => statement.evaluate(environment),
+            DeclarationKind::VariableDeclaration(variable_declaration) => {
                 variable_declaration.evaluate(environment)
             }
+            DeclarationKind::FunctionDeclaration(function) => {
+                /* the closure captures the frame it is declared in, so it can call itself */
+                let closure =
+                    Value::Function(Rc::new(Closure::new(function.clone(), environment.scope())));
+                let name = function.name.expect("declared functions are named");
+
+                environment.track(&closure);
+                environment.declare_variable(name, closure)?;
+                Ok(Completion::Normal)
+            }
         }
     }
 }
 
 impl Evaluate for VariableDeclaration {
-    fn evaluate(&self, environment: &mut Environment) -> Result<(), RuntimeError> {
+    fn evaluate(&self, environment: &mut Environment) -> Result<Completion, RuntimeError> {
         let value = match &self.value {
             Some(value) => Some(value.evaluate(environment)?),
             None => None,
         };
 
-        environment.declare_variable(&self.identifier, Value::from(value))?;
-        Ok(())
+        environment.declare_variable(self.identifier, Value::from(value))?;
+        Ok(Completion::Normal)
     }
 }
@@ -1,4 +1,12 @@
-use parser::grammar::{Binary, BinaryOperator, Expression, Primary, Unary, UnaryOperator};
+use parser::grammar::{
+    Assignment, AssignmentTarget, Binary, BinaryOperator, Call, Conditional, Expression, Fixity,
+    Increment, IncrementOperator, Index, Primary, Property, Unary, UnaryOperator, Variable,
+};
+
+use indexmap::IndexMap;
+
+use std::cell::RefCell;
+use std::rc::Rc;
 
 use crate::environment::Environment;
 use crate::evaluator::*;
@@ -6,12 +14,12 @@ use crate::evaluator::*;
 impl EvaluateValue for Expression {
     fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
         match self {
-            Expression::Assignment { identifier, value } => {
-                let value = value.evaluate(environment)?;
-
-                environment.assign_variable(identifier, value)?;
-                Ok(environment.lookup_variable(identifier).unwrap())
-            }
+            Expression::Assignment(assignment) => assignment.evaluate(environment),
+            Expression::Increment(increment) => increment.evaluate(environment),
+            Expression::Conditional(conditional) => conditional.evaluate(environment),
+            Expression::Call(call) => call.evaluate(environment),
+            Expression::Property(property) => property.evaluate(environment),
+            Expression::Index(index) => index.evaluate(environment),
             Expression::Unary(unary) => unary.evaluate(environment),
             Expression::Binary(binary) => binary.evaluate(environment),
             Expression::Primary(primary) => primary.evaluate(environment),
@@ -19,17 +27,241 @@ impl EvaluateValue for Expression {
     }
 }
 
+/// A storage location an assignment writes to. The target's subexpressions are evaluated once,
+/// when it is resolved, so compound assignments don't repeat their side effects.
+enum Place<'a> {
+    Variable(&'a Variable),
+    Element(Rc<RefCell<Vec<Value>>>, usize),
+    Entry(Rc<RefCell<IndexMap<MapKey, Value>>>, MapKey),
+}
+
+impl<'a> Place<'a> {
+    fn resolve(
+        target: &'a AssignmentTarget,
+        environment: &mut Environment,
+    ) -> EvaluatorResult<Self> {
+        match target {
+            AssignmentTarget::Identifier(variable) => Ok(Place::Variable(variable)),
+            AssignmentTarget::Property(property) => {
+                /* no value currently carries properties */
+                property.object.evaluate(environment)?;
+                Err(RuntimeError::UndefinedProperty)
+            }
+            AssignmentTarget::Index(index) => Place::index(index, environment),
+        }
+    }
+
+    fn index(index: &Index, environment: &mut Environment) -> EvaluatorResult<Self> {
+        match index.object.evaluate(environment)? {
+            Value::List(list) => {
+                let index = index.index.evaluate(environment)?;
+                let position = list_position(&list.borrow(), index)?;
+
+                Ok(Place::Element(list, position))
+            }
+            Value::Map(map) => {
+                let key = MapKey::try_from(index.index.evaluate(environment)?)?;
+                Ok(Place::Entry(map, key))
+            }
+            _ => Err(RuntimeError::NotIndexable),
+        }
+    }
+
+    fn get(&self, environment: &Environment) -> EvaluatorResult<Value> {
+        match self {
+            Place::Variable(variable) => environment
+                .lookup_variable(variable.name, variable.local)
+                .map(|value| value.clone())
+                .ok_or(RuntimeError::VariableDoesNotExist),
+            Place::Element(list, position) => Ok(list.borrow()[*position].clone()),
+            Place::Entry(map, key) => map
+                .borrow()
+                .get(key)
+                .cloned()
+                .ok_or(RuntimeError::UndefinedKey),
+        }
+    }
+
+    fn set(&self, environment: &mut Environment, value: Value) -> EvaluatorResult<Value> {
+        match self {
+            Place::Variable(variable) => {
+                environment.assign_variable(variable.name, variable.local, value)
+            }
+            Place::Element(list, position) => {
+                /* the element may have been removed while the assigned value was evaluated */
+                let mut list = list.borrow_mut();
+                let element = list
+                    .get_mut(*position)
+                    .ok_or(RuntimeError::IndexOutOfBounds)?;
+
+                *element = value.clone();
+                Ok(value)
+            }
+            Place::Entry(map, key) => {
+                environment.allocate(key.allocation() + std::mem::size_of::<Value>())?;
+                map.borrow_mut().insert(key.clone(), value.clone());
+                Ok(value)
+            }
+        }
+    }
+}
+
+/* converts an index value into a position within the bounds of the list */
+fn list_position(list: &[Value], index: Value) -> EvaluatorResult<usize> {
+    let Value::Numeric(index) = index else {
+        return Err(RuntimeError::InvalidIndex);
+    };
+
+    if index.fract() != 0.0 {
+        return Err(RuntimeError::InvalidIndex);
+    }
+
+    if index < 0.0 || index >= list.len() as f64 {
+        return Err(RuntimeError::IndexOutOfBounds);
+    }
+
+    Ok(index as usize)
+}
+
+impl EvaluateValue for Assignment {
+    fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
+        let place = Place::resolve(&self.target, environment)?;
+
+        let value = match &self.operator {
+            Some(operator) => {
+                let current = place.get(environment)?;
+                let right = self.value.evaluate(environment)?;
+
+                apply_binary_operator(operator, current, right)?
+            }
+            None => self.value.evaluate(environment)?,
+        };
+
+        place.set(environment, value)
+    }
+}
+
+impl EvaluateValue for Increment {
+    fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
+        let place = Place::resolve(&self.target, environment)?;
+
+        let Value::Numeric(old_value) = place.get(environment)? else {
+            return Err(RuntimeError::TypeError);
+        };
+
+        let new_value = match self.operator {
+            IncrementOperator::Increment => old_value + 1.0,
+            IncrementOperator::Decrement => old_value - 1.0,
+        };
+
+        place.set(environment, Value::Numeric(new_value))?;
+
+        match self.fixity {
+            Fixity::Prefix => Ok(Value::Numeric(new_value)),
+            Fixity::Postfix => Ok(Value::Numeric(old_value)),
+        }
+    }
+}
+
+impl EvaluateValue for Conditional {
+    fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
+        if self.condition.evaluate(environment)?.is_truthy() {
+            self.then.evaluate(environment)
+        } else {
+            self.else_.evaluate(environment)
+        }
+    }
+}
+
+impl EvaluateValue for Call {
+    fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
+        let callee = self.callee.evaluate(environment)?;
+
+        let arguments: Vec<Value> = self
+            .arguments
+            .iter()
+            .map(|arg| arg.evaluate(environment))
+            .collect::<Result<_, _>>()?;
+
+        call(&callee, arguments, environment)
+    }
+}
+
+pub(super) fn call(
+    callee: &Value,
+    arguments: Vec<Value>,
+    environment: &mut Environment,
+) -> EvaluatorResult<Value> {
+    match callee {
+        Value::NativeFunction(native) => {
+            if !native.arity().accepts(arguments.len()) {
+                return Err(RuntimeError::WrongNumberOfArguments);
+            }
+
+            let result = native.call(&mut Context::new(environment), &arguments)?;
+            environment.allocate(result.allocation())?;
+            environment.track(&result);
+
+            Ok(result)
+        }
+        Value::Function(closure) => {
+            if closure.arity() != arguments.len() {
+                return Err(RuntimeError::WrongNumberOfArguments);
+            }
+
+            call_function(closure, arguments, environment)
+        }
+        _ => Err(RuntimeError::NotCallable),
+    }
+}
+
+/* runs the body in a fresh frame on top of the closure's scope */
+fn call_function(
+    closure: &Closure,
+    arguments: Vec<Value>,
+    environment: &mut Environment,
+) -> EvaluatorResult<Value> {
+    environment.check_interrupt()?;
+
+    let mut environment = environment.enter_function(closure.scope.clone())?;
+
+    for (&parameter, argument) in closure.function.parameters.iter().zip(arguments) {
+        environment.declare_variable(parameter, argument)?;
+    }
+
+    for declaration in &closure.function.body {
+        if let Completion::Return(value) = declaration.evaluate(&mut environment)? {
+            return Ok(value);
+        }
+    }
+
+    Ok(Value::Nil)
+}
+
+impl EvaluateValue for Property {
+    fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
+        /* no value currently carries properties */
+        self.object.evaluate(environment)?;
+        Err(RuntimeError::UndefinedProperty)
+    }
+}
+
+impl EvaluateValue for Index {
+    fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
+        Place::index(self, environment)?.get(environment)
+    }
+}
+
 impl EvaluateValue for Unary {
     fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
         match self.operator {
             UnaryOperator::Negate => match self.right.evaluate(environment)? {
                 Value::Numeric(value) => Ok(Value::Numeric(-value)),
-                _ => todo!("- not yet supported for types"),
-            },
-            UnaryOperator::Not => match self.right.evaluate(environment)? {
-                Value::Boolean(value) => Ok(Value::Boolean(!value)),
-                _ => todo!("! operator not yet supported for types"),
+                _ => Err(RuntimeError::TypeError),
             },
+            UnaryOperator::Not => Ok(Value::Boolean(
+                !self.right.evaluate(environment)?.is_truthy(),
+            )),
         }
     }
 }
@@ -37,157 +269,127 @@ impl EvaluateValue for Unary {
 impl EvaluateValue for Binary {
     fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
         match self.operator {
-            BinaryOperator::Multiplication => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Numeric(left_value * right_value))
-                    }
-                    _ => todo!("* operator not supported for types"),
-                }
-            }
-            BinaryOperator::Division => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Numeric(left_value / right_value))
-                    }
-                    _ => todo!("/ operator not supported for types"),
-                }
-            }
-            BinaryOperator::Addition => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Numeric(left_value + right_value))
-                    }
-                    (Value::String_(left_value), Value::String_(right_value)) => {
-                        Ok(Value::String_(left_value + &right_value))
-                    }
-                    _ => todo!("+ operator not supported for types"),
-                }
-            }
-            BinaryOperator::Subtraction => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Numeric(left_value - right_value))
-                    }
-                    _ => todo!("- operator not supported for types"),
+            /* like in lox, the logical operators yield one of their operands rather than a boolean */
+            BinaryOperator::And => {
+                let left = self.left.evaluate(environment)?;
+
+                match left.is_truthy() {
+                    true => self.right.evaluate(environment),
+                    false => Ok(left),
                 }
             }
-            BinaryOperator::Equality => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Boolean(left_value == right_value))
-                    }
-                    (Value::Boolean(left_value), Value::Boolean(right_value)) => {
-                        Ok(Value::Boolean(left_value == right_value))
-                    }
-                    _ => todo!("== operator not supported for types"),
+            BinaryOperator::Or => {
+                let left = self.left.evaluate(environment)?;
+
+                match left.is_truthy() {
+                    true => Ok(left),
+                    false => self.right.evaluate(environment),
                 }
             }
-            BinaryOperator::And => {
-                let lhs = self.left.evaluate(environment)?;
+            _ => {
+                let left = self.left.evaluate(environment)?;
+                let right = self.right.evaluate(environment)?;
 
-                match lhs {
-                    Value::Boolean(lhs_bool) if !lhs_bool => Ok(lhs),
-                    Value::Boolean(lhs_bool) if lhs_bool => {
-                        let rhs = self.right.evaluate(environment)?;
+                let result = apply_binary_operator(&self.operator, left, right)?;
+                environment.allocate(result.allocation())?;
+                environment.track(&result);
 
-                        match rhs {
-                            Value::Boolean(_) => Ok(rhs),
-                            _ => todo!("or operator not supported for types"),
-                        }
-                    }
-                    _ => todo!("or operator not supported for types"),
-                }
+                Ok(result)
             }
-            BinaryOperator::Or => {
-                let lhs = self.left.evaluate(environment)?;
+        }
+    }
+}
 
-                match lhs {
-                    Value::Boolean(lhs_bool) if lhs_bool => Ok(lhs),
-                    Value::Boolean(lhs_bool) if !lhs_bool => {
-                        let rhs = self.right.evaluate(environment)?;
+/* applies any operator other than the short-circuiting and/or to evaluated operands */
+fn apply_binary_operator(
+    operator: &BinaryOperator,
+    left: Value,
+    right: Value,
+) -> EvaluatorResult<Value> {
+    match operator {
+        BinaryOperator::Multiplication => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Numeric(left_value * right_value))
+            }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::Division => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Numeric(left_value / right_value))
+            }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::IntegerDivision => match (left, right) {
+            (Value::Numeric(_), Value::Numeric(0.0)) => Err(RuntimeError::DivisionByZero),
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Numeric((left_value / right_value).floor()))
+            }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::Modulo => match (left, right) {
+            (Value::Numeric(_), Value::Numeric(0.0)) => Err(RuntimeError::DivisionByZero),
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                /* floored modulo: the result takes the sign of the divisor, so that
+                 * a == (a // b) * b + a % b holds for negative operands too */
+                let remainder = left_value % right_value;
 
-                        match rhs {
-                            Value::Boolean(_) => Ok(rhs),
-                            _ => todo!("or operator not supported for types"),
-                        }
-                    }
-                    _ => todo!("or operator not supported for types"),
+                if remainder != 0.0 && (remainder < 0.0) != (right_value < 0.0) {
+                    Ok(Value::Numeric(remainder + right_value))
+                } else {
+                    Ok(Value::Numeric(remainder))
                 }
             }
-            BinaryOperator::Inequality => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Boolean(left_value != right_value))
-                    }
-                    (Value::Boolean(left_value), Value::Boolean(right_value)) => {
-                        Ok(Value::Boolean(left_value != right_value))
-                    }
-                    _ => todo!("!= operator not supported for types"),
-                }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::Exponentiation => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Numeric(left_value.powf(right_value)))
             }
-            BinaryOperator::GreaterThan => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Boolean(left_value > right_value))
-                    }
-                    _ => todo!("> operator not supported for types"),
-                }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::Addition => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Numeric(left_value + right_value))
             }
-            BinaryOperator::GreaterThanOrEqualTo => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Boolean(left_value >= right_value))
-                    }
-                    _ => todo!(">= operator not supported for types"),
-                }
+            (Value::String_(left_value), Value::String_(right_value)) => {
+                Ok(Value::from([&*left_value, &*right_value].concat()))
             }
-            BinaryOperator::LessThan => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Boolean(left_value < right_value))
-                    }
-                    _ => todo!("< operator not supported for types"),
-                }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::Subtraction => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Numeric(left_value - right_value))
             }
-            BinaryOperator::LessThanOrEqualTo => {
-                match (
-                    self.left.evaluate(environment)?,
-                    self.right.evaluate(environment)?,
-                ) {
-                    (Value::Numeric(left_value), Value::Numeric(right_value)) => {
-                        Ok(Value::Boolean(left_value <= right_value))
-                    }
-                    _ => todo!("<= operator not supported for types"),
-                }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::Equality => Ok(Value::Boolean(left == right)),
+        BinaryOperator::Inequality => Ok(Value::Boolean(left != right)),
+        BinaryOperator::GreaterThan => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Boolean(left_value > right_value))
+            }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::GreaterThanOrEqualTo => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Boolean(left_value >= right_value))
+            }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::LessThan => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Boolean(left_value < right_value))
+            }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::LessThanOrEqualTo => match (left, right) {
+            (Value::Numeric(left_value), Value::Numeric(right_value)) => {
+                Ok(Value::Boolean(left_value <= right_value))
             }
+            _ => Err(RuntimeError::TypeError),
+        },
+        BinaryOperator::And | BinaryOperator::Or => {
+            unreachable!("logical operators short-circuit in Binary::evaluate")
         }
     }
 }
@@ -195,35 +397,532 @@ impl EvaluateValue for Binary {
 impl EvaluateValue for Primary {
     fn evaluate(&self, environment: &mut Environment) -> EvaluatorResult<Value> {
         match self {
-            Primary::Call {
-                callable,
-                arguments,
-            } => {
-                let Value::Callable(callable) = callable.evaluate(environment)? else {
-                    return Err(RuntimeError::NotCallable);
-                };
-
-                if callable.arity() != arguments.len() {
-                    return Err(RuntimeError::WrongNumberOfArguments);
-                }
-
-                let arguments = arguments
-                    .into_iter()
-                    .map(|arg| arg.evaluate(environment))
-                    .collect::<Result<_, _>>()?;
-
-                Ok(callable.call(&arguments))
-            }
             Primary::True => Ok(Value::Boolean(true)),
             Primary::False => Ok(Value::Boolean(false)),
             Primary::Nil => Ok(Value::Nil),
             Primary::Number(value) => Ok(Value::Numeric(*value)),
             Primary::String_(value) => Ok(Value::String_(value.clone())),
-            Primary::Identifier(identifier) => match environment.lookup_variable(identifier) {
-                Some(value) => Ok(value.clone()),
-                None => Err(RuntimeError::VariableDoesNotExist),
-            },
+            Primary::Identifier(variable) => {
+                match environment.lookup_variable(variable.name, variable.local) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(RuntimeError::VariableDoesNotExist),
+                }
+            }
             Primary::Grouping(expression) => expression.evaluate(environment),
+            Primary::List(elements) => {
+                let list = Value::list(
+                    elements
+                        .iter()
+                        .map(|element| element.evaluate(environment))
+                        .collect::<Result<_, _>>()?,
+                );
+                environment.allocate(list.allocation())?;
+                environment.track(&list);
+
+                Ok(list)
+            }
+            Primary::Map(entries) => {
+                let mut map = IndexMap::with_capacity(entries.len());
+
+                for (key, value) in entries {
+                    let key = MapKey::try_from(key.evaluate(environment)?)?;
+                    map.insert(key, value.evaluate(environment)?);
+                }
+
+                let map = Value::map(map);
+                environment.allocate(map.allocation())?;
+                environment.track(&map);
+
+                Ok(map)
+            }
+            Primary::Function(function) => {
+                let closure =
+                    Value::Function(Rc::new(Closure::new(function.clone(), environment.scope())));
+                environment.track(&closure);
+
+                Ok(closure)
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TRUTHY, evaluate, evaluate_number, result_of, run_with};
+
+    #[test]
+    fn test_modulo() {
+        assert_eq!(evaluate_number("7 % 3"), 1.0);
+        assert_eq!(evaluate_number("7.5 % 2"), 1.5);
+        assert_eq!(evaluate_number("0 - 7 % 3"), -1.0);
+        assert_eq!(evaluate_number("(0 - 7) % 3"), 2.0);
+        assert_eq!(evaluate_number("7 % (0 - 3)"), -2.0);
+        assert_eq!(evaluate_number("(0 - 6) % 3"), 0.0);
+    }
+
+    #[test]
+    fn test_integer_division() {
+        assert_eq!(evaluate_number("7 // 2"), 3.0);
+        assert_eq!(evaluate_number("(0 - 7) // 2"), -4.0);
+        assert_eq!(evaluate_number("7 // (0 - 2)"), -4.0);
+    }
+
+    #[test]
+    fn test_division_identity() {
+        for (a, b) in [(7, 3), (-7, 3), (7, -3), (-7, -3)] {
+            let code = format!("(({a}) // ({b})) * ({b}) + ({a}) % ({b})");
+            assert_eq!(evaluate_number(&code), a as f64);
+        }
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        assert!(matches!(
+            evaluate("1 % 0"),
+            Err(RuntimeError::DivisionByZero)
+        ));
+        assert!(matches!(
+            evaluate("1 // 0"),
+            Err(RuntimeError::DivisionByZero)
+        ));
+        assert_eq!(evaluate_number("1 / 0"), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_exponentiation() {
+        assert_eq!(evaluate_number("2 ** 10"), 1024.0);
+        assert_eq!(evaluate_number("2 ** 3 ** 2"), 512.0);
+        assert_eq!(evaluate_number("2 ** (0 - 1)"), 0.5);
+        assert!(evaluate_number("(0 - 8) ** 0.5").is_nan());
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        assert_eq!(evaluate_number("(x = 10) + (x += 5) + x"), 40.0);
+        assert_eq!(evaluate_number("(x = 10) * 0 + (x -= 4)"), 6.0);
+        assert_eq!(evaluate_number("(x = 10) * 0 + (x *= 3)"), 30.0);
+        assert_eq!(evaluate_number("(x = 10) * 0 + (x /= 4)"), 2.5);
+        assert_eq!(evaluate_number("(x = 10) * 0 + (x %= 4)"), 2.0);
+    }
+
+    #[test]
+    fn test_compound_assignment_type_error() {
+        assert!(matches!(
+            evaluate("(x = true) and (x %= 2)"),
+            Err(RuntimeError::TypeError)
+        ));
+        assert!(matches!(
+            evaluate("(x = true) and (x += \"a\")"),
+            Err(RuntimeError::TypeError)
+        ));
+    }
+
+    #[test]
+    fn test_equality() {
+        let cases = [
+            ("\"a\" == \"a\"", true),
+            ("\"a\" != \"b\"", true),
+            ("nil == nil", true),
+            ("true == true", true),
+            ("1 == \"1\"", false),
+            ("nil == false", false),
+            ("[1] == [1]", false),
+            ("(x = [1]) == x", true),
+            ("len == len", true),
+            ("{} != {}", true),
+        ];
+
+        for (expression, expected) in cases {
+            assert!(
+                matches!(evaluate(expression), Ok(Value::Boolean(result)) if result == expected),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_increment() {
+        assert_eq!(evaluate_number("(x = 1) * 0 + x++"), 1.0);
+        assert_eq!(evaluate_number("(x = 1) * 0 + ++x"), 2.0);
+        assert_eq!(evaluate_number("(x = 1) * 0 + x-- + x"), 1.0);
+        assert_eq!(evaluate_number("(x = 1) * 0 + --x + x"), 0.0);
+    }
+
+    #[test]
+    fn test_assignment_to_undeclared_variable() {
+        assert!(matches!(
+            evaluate("y += 1"),
+            Err(RuntimeError::VariableDoesNotExist)
+        ));
+        assert!(matches!(
+            evaluate("y++"),
+            Err(RuntimeError::VariableDoesNotExist)
+        ));
+    }
+
+    #[test]
+    fn test_list_literal() {
+        assert_eq!(evaluate("[]").unwrap().to_string(), "[]");
+        assert_eq!(
+            evaluate("[1, \"a\", [true, nil]]").unwrap().to_string(),
+            "[1, \"a\", [true, nil]]"
+        );
+    }
+
+    #[test]
+    fn test_list_indexing() {
+        assert_eq!(evaluate_number("[1, 2, 3][1]"), 2.0);
+        assert_eq!(evaluate_number("[[1, 2], [3, 4]][1][0]"), 3.0);
+        assert!(matches!(
+            evaluate("[1][1]"),
+            Err(RuntimeError::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            evaluate("[1][0 - 1]"),
+            Err(RuntimeError::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            evaluate("[1][0.5]"),
+            Err(RuntimeError::InvalidIndex)
+        ));
+        assert!(matches!(
+            evaluate("[1][\"0\"]"),
+            Err(RuntimeError::InvalidIndex)
+        ));
+        assert!(matches!(evaluate("1[0]"), Err(RuntimeError::NotIndexable)));
+    }
+
+    #[test]
+    fn test_list_index_assignment() {
+        let result =
+            result_of("var xs = [1, 2, 3]; xs[0] = 10; xs[2] += xs[1]++; var result = xs;");
+        assert_eq!(result.unwrap().to_string(), "[10, 3, 5]");
+
+        assert!(matches!(
+            result_of("var xs = []; xs[0] = 1;"),
+            Err(RuntimeError::IndexOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn test_lists_are_shared() {
+        let result = result_of("var xs = [1]; var ys = xs; ys[0] = 2; var result = xs[0];");
+        assert!(matches!(result, Ok(Value::Numeric(2.0))));
+    }
+
+    #[test]
+    fn test_list_natives() {
+        let result = result_of(
+            "var xs = []; push(xs, 1); push(xs, 2); var result = [len(xs), pop(xs), xs];",
+        );
+        assert_eq!(result.unwrap().to_string(), "[2, 2, [1]]");
+
+        assert!(matches!(evaluate("pop([])"), Ok(Value::Nil)));
+        assert_eq!(evaluate_number("len(\"héllo\")"), 5.0);
+        assert!(matches!(evaluate("len(1)"), Err(RuntimeError::TypeError)));
+        assert!(matches!(
+            evaluate("push(1, 1)"),
+            Err(RuntimeError::TypeError)
+        ));
+    }
+
+    #[test]
+    fn test_print_self_containing_list() {
+        let result = result_of("var xs = [1]; push(xs, xs); var result = [xs, xs];");
+        assert_eq!(result.unwrap().to_string(), "[[1, [...]], [1, [...]]]");
+    }
+
+    #[test]
+    fn test_map_literal() {
+        assert_eq!(evaluate("{}").unwrap().to_string(), "{}");
+        assert_eq!(
+            evaluate("{\"a\": 1, 2: [true], nil: nil, false: \"b\"}")
+                .unwrap()
+                .to_string(),
+            "{\"a\": 1, 2: [true], nil: nil, false: \"b\"}"
+        );
+        assert!(matches!(
+            evaluate("{[]: 1}"),
+            Err(RuntimeError::UnhashableKey)
+        ));
+    }
+
+    #[test]
+    fn test_print_self_containing_map() {
+        let result = result_of("var m = {}; m[\"self\"] = m; m[\"list\"] = [m]; var result = m;");
+        assert_eq!(
+            result.unwrap().to_string(),
+            "{\"self\": {...}, \"list\": [{...}]}"
+        );
+    }
+
+    #[test]
+    fn test_map_lookup() {
+        assert_eq!(evaluate_number("{\"a\": 1, \"b\": 2}[\"b\"]"), 2.0);
+        assert_eq!(evaluate_number("{0: 1}[0 * (0 - 1)]"), 1.0);
+        assert!(matches!(
+            evaluate("{\"a\": 1}[\"b\"]"),
+            Err(RuntimeError::UndefinedKey)
+        ));
+        assert!(matches!(
+            evaluate("{\"a\": 1}[{}]"),
+            Err(RuntimeError::UnhashableKey)
+        ));
+    }
+
+    #[test]
+    fn test_map_assignment() {
+        let result =
+            result_of("var m = {\"a\": 1}; m[\"a\"] += 1; m[true] = \"t\"; var result = m;");
+        assert_eq!(result.unwrap().to_string(), "{\"a\": 2, true: \"t\"}");
+
+        assert!(matches!(
+            result_of("var m = {}; m[\"a\"] += 1;"),
+            Err(RuntimeError::UndefinedKey)
+        ));
+    }
+
+    #[test]
+    fn test_map_natives() {
+        let result = result_of(
+            "var m = {\"a\": 1, \"b\": 2, \"c\": 3};
+            var removed = remove(m, \"b\");
+            var result = [keys(m), values(m), has(m, \"a\"), has(m, \"b\"), removed, len(m)];",
+        );
+        assert_eq!(
+            result.unwrap().to_string(),
+            "[[\"a\", \"c\"], [1, 3], true, false, 2, 2]"
+        );
+
+        assert!(matches!(evaluate("remove({}, 1)"), Ok(Value::Nil)));
+        assert!(matches!(
+            evaluate("has({}, [])"),
+            Err(RuntimeError::UnhashableKey)
+        ));
+        assert!(matches!(evaluate("keys([])"), Err(RuntimeError::TypeError)));
+    }
+
+    #[test]
+    fn test_arithmetic_type_error() {
+        for expression in [
+            "\"a\" % 2",
+            "2 ** true",
+            "\"a\" + 1",
+            "\"a\" * 2",
+            "-\"a\"",
+            "\"a\" < \"b\"",
+            "nil - 1",
+            "[1] / 2",
+        ] {
+            assert!(
+                matches!(evaluate(expression), Err(RuntimeError::TypeError)),
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_function_expression() {
+        assert_eq!(evaluate_number("fun (a, b) { return a + b; }(1, 2)"), 3.0);
+        assert_eq!(evaluate_number("((a, b) => a * b)(3, 4)"), 12.0);
+        assert_eq!(evaluate_number("(a => a + 1)(1)"), 2.0);
+        assert_eq!(evaluate_number("(() => { return 7; })()"), 7.0);
+        assert!(matches!(evaluate("fun () {}()"), Ok(Value::Nil)));
+        assert_eq!(evaluate("fun () {}").unwrap().to_string(), "<fn>");
+    }
+
+    #[test]
+    fn test_closures() {
+        let result = result_of(
+            "fun makeCounter() {
+                var count = 0;
+                return () => ++count;
+            }
+            var counter = makeCounter();
+            counter();
+            counter();
+            var result = [counter(), makeCounter()()];",
+        );
+        assert_eq!(result.unwrap().to_string(), "[3, 1]");
+
+        let result = result_of("var makeAdder = a => b => a + b; var result = makeAdder(1)(2);");
+        assert!(matches!(result, Ok(Value::Numeric(3.0))));
+    }
+
+    #[test]
+    fn test_recursion() {
+        let result = result_of(
+            "fun fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); }
+            var result = fib(10);",
+        );
+        assert!(matches!(result, Ok(Value::Numeric(55.0))));
+    }
+
+    #[test]
+    fn test_closures_bind_statically() {
+        let result = result_of(
+            "var a = \"global\";
+            var result;
+            {
+                fun show() { return a; }
+                var first = show();
+                var a = \"block\";
+                result = [first, show(), a];
+            }",
+        );
+        assert_eq!(
+            result.unwrap().to_string(),
+            "[\"global\", \"global\", \"block\"]"
+        );
+    }
+
+    #[test]
+    fn test_higher_order_functions() {
+        let result = result_of(
+            "fun map(xs, f) {
+                var ys = [];
+                for (var i = 0; i < len(xs); i = i + 1) push(ys, f(xs[i]));
+                return ys;
+            }
+            fun filter(xs, keep) {
+                var ys = [];
+                for (var i = 0; i < len(xs); i = i + 1) if (keep(xs[i])) push(ys, xs[i]);
+                return ys;
+            }
+            var result = map(filter([1, 2, 3, 4], x => x % 2 == 0), fun (x) { return x * 10; });",
+        );
+        assert_eq!(result.unwrap().to_string(), "[20, 40]");
+    }
+
+    #[test]
+    fn test_function_arity() {
+        assert!(matches!(
+            evaluate("(a => a)()"),
+            Err(RuntimeError::WrongNumberOfArguments)
+        ));
+        assert!(matches!(evaluate("1()"), Err(RuntimeError::NotCallable)));
+    }
+
+    #[test]
+    fn test_conditional_truthiness() {
+        for value in TRUTHY {
+            assert_eq!(evaluate_number(&format!("{value} ? 1 : 2")), 1.0);
+        }
+
+        assert_eq!(evaluate_number("nil ? 1 : 2"), 2.0);
+        assert_eq!(evaluate_number("false ? 1 : 2"), 2.0);
+    }
+
+    #[test]
+    fn test_not_truthiness() {
+        for value in TRUTHY {
+            assert!(matches!(
+                evaluate(&format!("!{value}")),
+                Ok(Value::Boolean(false))
+            ));
+        }
+
+        assert!(matches!(evaluate("!nil"), Ok(Value::Boolean(true))));
+        assert!(matches!(evaluate("!false"), Ok(Value::Boolean(true))));
+    }
+
+    #[test]
+    fn test_logical_truthiness() {
+        assert_eq!(evaluate_number("0 and 1"), 1.0);
+        assert_eq!(evaluate_number("0 or 1"), 0.0);
+        assert_eq!(evaluate_number("nil or 1"), 1.0);
+        assert!(matches!(evaluate("nil and 1"), Ok(Value::Nil)));
+        assert!(matches!(evaluate("false or nil"), Ok(Value::Nil)));
+        assert_eq!(evaluate("\"\" and \"a\"").unwrap().to_string(), "a");
+    }
+
+    #[test]
+    fn test_logical_short_circuit() {
+        assert!(matches!(evaluate("nil and undefined"), Ok(Value::Nil)));
+        assert_eq!(evaluate_number("1 or undefined"), 1.0);
+    }
+
+    #[test]
+    fn test_stateful_native() {
+        let mut evaluator = Evaluator::new();
+        let count = Rc::new(RefCell::new(0.0));
+
+        let counter = count.clone();
+        evaluator
+            .define_native("tick", Arity::Exactly(0), move |_, _| {
+                *counter.borrow_mut() += 1.0;
+                Ok(Value::Numeric(*counter.borrow()))
+            })
+            .unwrap();
+
+        let result = run_with(&mut evaluator, "tick(); tick(); var result = tick();");
+        assert!(matches!(result, Ok(Value::Numeric(3.0))));
+        assert_eq!(*count.borrow(), 3.0);
+    }
+
+    #[test]
+    fn test_variadic_native() {
+        let sum = |evaluator: &mut Evaluator| {
+            evaluator
+                .define_native("sum", Arity::AtLeast(1), |_, arguments| {
+                    let mut sum = 0.0;
+
+                    for argument in arguments {
+                        let Value::Numeric(value) = argument else {
+                            return Err(RuntimeError::TypeError);
+                        };
+
+                        sum += value;
+                    }
+
+                    Ok(Value::Numeric(sum))
+                })
+                .unwrap();
+        };
+
+        let mut evaluator = Evaluator::new();
+        sum(&mut evaluator);
+        let result = run_with(&mut evaluator, "var result = [sum(1), sum(1, 2, 3)];");
+        assert_eq!(result.unwrap().to_string(), "[1, 6]");
+
+        let mut evaluator = Evaluator::new();
+        sum(&mut evaluator);
+        assert!(matches!(
+            run_with(&mut evaluator, "sum();"),
+            Err(RuntimeError::WrongNumberOfArguments)
+        ));
+
+        let mut evaluator = Evaluator::new();
+        sum(&mut evaluator);
+        assert!(matches!(
+            run_with(&mut evaluator, "sum(1, \"a\");"),
+            Err(RuntimeError::TypeError)
+        ));
+    }
+
+    #[test]
+    fn test_native_calls_back() {
+        let mut evaluator = Evaluator::new();
+        evaluator
+            .define_native("twice", Arity::Exactly(2), |context, arguments| {
+                let once = context.call(&arguments[0], vec![arguments[1].clone()])?;
+                context.call(&arguments[0], vec![once])
+            })
+            .unwrap();
+
+        let result = run_with(&mut evaluator, "var result = twice(x => x * 3, 2);");
+        assert!(matches!(result, Ok(Value::Numeric(18.0))));
+    }
+
+    #[test]
+    fn test_native_redefinition() {
+        let mut evaluator = Evaluator::new();
+
+        assert!(matches!(
+            evaluator.define_native("len", Arity::Exactly(1), |_, _| Ok(Value::Nil)),
+            Err(RuntimeError::VariableRedefinition)
+        ));
+        assert_eq!(evaluate("len").unwrap().to_string(), "<native fn len>");
+    }
+}
@@ -2,10 +2,12 @@ use crate::evaluator::*;
 use parser::grammar::*;
 
 impl Evaluate for Statement {
-    fn evaluate(&self, environment: &mut Environment) -> Result<(), RuntimeError> {
+    fn evaluate(&self, environment: &mut Environment) -> Result<Completion, RuntimeError> {
+        environment.step()?;
+
         match self {
             Statement::ExpressionStatement(expression) => {
-                expression.evaluate(environment).map(|_| ())
+                expression.evaluate(environment).map(|_| Completion::Normal)
             }
             Statement::ForStatement {
                 initializer,
@@ -13,13 +15,13 @@ impl Evaluate for Statement {
                 expression,
                 body,
             } => {
-                environment.push();
+                let mut environment = environment.enter_scope();
 
-                initializer.evaluate(environment)?;
+                initializer.evaluate(&mut environment)?;
 
                 loop {
                     let condition = match condition {
-                        Some(condition) => condition.evaluate(environment)?,
+                        Some(condition) => condition.evaluate(&mut environment)?,
                         None => Value::Boolean(true),
                     };
 
@@ -27,62 +29,222 @@ impl Evaluate for Statement {
                         break;
                     }
 
-                    body.evaluate(environment)?;
+                    environment.check_interrupt()?;
 
-                    match expression {
-                        Some(expression) => {
-                            let _ = expression.evaluate(environment)?;
-                        }
-                        None => (),
-                    };
+                    if let Completion::Return(value) = body.evaluate(&mut environment)? {
+                        return Ok(Completion::Return(value));
+                    }
+
+                    if let Some(expression) = expression {
+                        expression.evaluate(&mut environment)?;
+                    }
                 }
 
-                Ok(())
+                Ok(Completion::Normal)
             }
             Statement::Block(block) => {
-                environment.push();
+                let mut environment = environment.enter_scope();
 
                 for statement in &block.statements {
-                    statement.evaluate(environment)?;
+                    if let Completion::Return(value) = statement.evaluate(&mut environment)? {
+                        return Ok(Completion::Return(value));
+                    }
                 }
 
-                environment.pop();
-
-                Ok(())
+                Ok(Completion::Normal)
             }
             Statement::IfStatement {
                 condition,
                 then,
                 else_,
             } => {
-                let Value::Boolean(condition) = condition.evaluate(environment)? else {
-                    return Err(RuntimeError::TypeError);
-                };
-
-                if condition {
+                if condition.evaluate(environment)?.is_truthy() {
                     then.evaluate(environment)
                 } else {
-                    else_.as_ref().map_or(Ok(()), |e| e.evaluate(environment))
+                    else_
+                        .as_ref()
+                        .map_or(Ok(Completion::Normal), |e| e.evaluate(environment))
                 }
             }
             Statement::WhileStatement { condition, body } => {
                 while condition.evaluate(environment)?.is_truthy() {
-                    body.evaluate(environment)?;
+                    environment.check_interrupt()?;
+
+                    if let Completion::Return(value) = body.evaluate(environment)? {
+                        return Ok(Completion::Return(value));
+                    }
                 }
-                Ok(())
+                Ok(Completion::Normal)
             }
             Statement::PrintStatement(expression) => {
-                println!("{}", expression.evaluate(environment)?);
-                Ok(())
+                let value = expression.evaluate(environment)?;
+                environment.print(&value)?;
+
+                Ok(Completion::Normal)
+            }
+            Statement::ReturnStatement(value) => {
+                let value = match value {
+                    Some(value) => value.evaluate(environment)?,
+                    None => Value::Nil,
+                };
+
+                Ok(Completion::Return(value))
             }
         }
     }
 }
 
 impl Evaluate for ForLoopInitializer {
-    fn evaluate(&self, environment: &mut Environment) -> Result<(), RuntimeError> {
+    fn evaluate(&self, environment: &mut Environment) -> Result<Completion, RuntimeError> {
         match self {
             Self::Declaration(variable_declaration) => variable_declaration.evaluate(environment),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OutputBuffer;
+    use crate::test_util::{TRUTHY, parse};
+
+    use lexer::Symbol;
+
+    /* runs a program, ignoring whether it fails, and returns the number of scopes left open */
+    fn depth_after(code: &str) -> usize {
+        let mut evaluator = Evaluator::new();
+        let _ = evaluator.evaluate(&parse(code));
+
+        evaluator.environment.depth()
+    }
+
+    #[test]
+    fn test_scopes_closed_after_success() {
+        assert_eq!(depth_after("1 + 1;"), 0);
+        assert_eq!(depth_after("print 1;"), 0);
+        assert_eq!(depth_after("{ var a = 1; { var b = a; } }"), 0);
+        assert_eq!(
+            depth_after("for (var i = 0; i < 3; i = i + 1) { var a = i; }"),
+            0
+        );
+        assert_eq!(
+            depth_after("var i = 0; while (i < 3) { var a = i; i = i + 1; }"),
+            0
+        );
+        assert_eq!(
+            depth_after("if (true) { var a = 1; } else { var b = 2; }"),
+            0
+        );
+        assert_eq!(depth_after("fun f() { { var a = 1; } } f();"), 0);
+    }
+
+    #[test]
+    fn test_scopes_closed_after_return() {
+        assert_eq!(depth_after("fun f() { { { return 1; } } } f();"), 0);
+        assert_eq!(
+            depth_after("fun f() { for (var i = 0; i < 3; i = i + 1) { return i; } } f();"),
+            0
+        );
+        assert_eq!(depth_after("fun f() { while (true) { return; } } f();"), 0);
+    }
+
+    #[test]
+    fn test_scopes_closed_after_error() {
+        assert_eq!(depth_after("{ var a = 1; { undefined; } }"), 0);
+        assert_eq!(depth_after("for (var i = 0; undefined; i = i + 1) {}"), 0);
+        assert_eq!(depth_after("for (var i = 0; i < 3; undefined) {}"), 0);
+        assert_eq!(
+            depth_after("for (var i = 0; i < 3; i = i + 1) { undefined; }"),
+            0
+        );
+        assert_eq!(depth_after("while (true) { undefined; }"), 0);
+        assert_eq!(depth_after("if (true) { undefined; }"), 0);
+        assert_eq!(depth_after("fun f() { { undefined; } } { f(); }"), 0);
+    }
+
+    #[test]
+    fn test_unresolved_ast() {
+        let tokens = lexer::tokenize("{ var a = 1; print a; }").unwrap();
+        let ast = parser::Ast::new(tokens.into_iter()).unwrap();
+
+        assert!(matches!(
+            Evaluator::new().evaluate(&ast),
+            Err(RuntimeError::UnresolvedAst)
+        ));
+    }
+
+    #[test]
+    fn test_declaration_after_error_is_global() {
+        let mut evaluator = Evaluator::new();
+
+        assert!(evaluator.evaluate(&parse("{ { undefined; } }")).is_err());
+        evaluator.evaluate(&parse("var a = 1;")).unwrap();
+
+        assert!(matches!(
+            evaluator
+                .environment
+                .lookup_variable(Symbol::intern("a"), None)
+                .as_deref(),
+            Some(Value::Numeric(1.0))
+        ));
+    }
+
+    /* runs a statement for a condition, which should leave `result` set to true */
+    fn taken(statement: &str, condition: &str) -> bool {
+        let code = format!("var result = false; {}", statement.replace("$", condition));
+
+        let mut evaluator = Evaluator::new();
+        evaluator.evaluate(&parse(&code)).unwrap();
+
+        let result = evaluator
+            .environment
+            .lookup_variable(Symbol::intern("result"), None);
+        matches!(result.as_deref(), Some(Value::Boolean(true)))
+    }
+
+    fn assert_truthiness(statement: &str) {
+        for value in TRUTHY {
+            assert!(taken(statement, value), "{value} should be truthy");
+        }
+
+        assert!(!taken(statement, "nil"));
+        assert!(!taken(statement, "false"));
+    }
+
+    #[test]
+    fn test_if_truthiness() {
+        assert_truthiness("if ($) result = true;");
+        assert_truthiness("if ($) result = true; else result = false;");
+    }
+
+    #[test]
+    fn test_while_truthiness() {
+        assert_truthiness("var c = $; while (c) { result = true; c = false; }");
+    }
+
+    #[test]
+    fn test_for_truthiness() {
+        assert_truthiness("for (var c = $; c; c = false) result = true;");
+    }
+
+    /* runs a program and returns everything it printed */
+    fn output(code: &str) -> String {
+        let mut evaluator = Evaluator::new();
+        let output = OutputBuffer::new();
+
+        evaluator.set_output(output.clone());
+        evaluator.evaluate(&parse(code)).unwrap();
+
+        output.contents()
+    }
+
+    #[test]
+    fn test_print() {
+        assert_eq!(output("print 1; print \"a\"; print nil;"), "1\na\nnil\n");
+        assert_eq!(
+            output("for (var i = 0; i < 3; i = i + 1) print i;"),
+            "0\n1\n2\n"
+        );
+        assert_eq!(output("print fun () {};"), "<fn>\n");
+    }
+}
@@ -0,0 +1,463 @@
+use crate::environment::Frame;
+use crate::evaluator::{Closure, MapKey, Value};
+
+use indexmap::IndexMap;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+/* the evaluator collects once it has tracked this many objects since the last collection, or
+ * twice as many as survived it, whichever is more */
+const MIN_THRESHOLD: usize = 1024;
+
+/// What the garbage collector has done so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    pub collections: usize,
+    pub objects_freed: usize,
+    /// Roughly how much memory the freed objects took up.
+    pub bytes_freed: usize,
+}
+
+/// Lists, maps, closures and frames are reference counted, which frees them as soon as nothing
+/// refers to them but leaks them once they refer to each other in a cycle, such as a closure
+/// stored in the frame it captured. The heap keeps a weak reference to each such object the
+/// evaluator creates or is handed, and frees the cycles among them with mark and sweep.
+///
+/// The roots are found rather than listed: a tracked object is a root if something outside the
+/// tracked objects holds a reference to it, such as the globals, the environment chain, the call
+/// stack or a value the evaluator is in the middle of computing. Everything that can't be reached
+/// from a root is garbage, and sweeping it empties it, which breaks its cycles so that reference
+/// counting can free it. An object the heap never saw only ever counts as a root, so it can keep
+/// garbage alive but never cause live objects to be freed.
+pub struct Heap {
+    objects: HashMap<usize, Tracked>,
+    /* objects tracked since the last collection */
+    allocations: usize,
+    threshold: usize,
+    stress: bool,
+    stats: GcStats,
+}
+
+enum Tracked {
+    List(Weak<RefCell<Vec<Value>>>),
+    Map(Weak<RefCell<IndexMap<MapKey, Value>>>),
+    Closure(Weak<Closure>),
+    Frame(Weak<RefCell<Frame>>),
+}
+
+/* a tracked object that is still alive, kept alive for the duration of a collection */
+enum Object {
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<IndexMap<MapKey, Value>>>),
+    Closure(Rc<Closure>),
+    Frame(Rc<RefCell<Frame>>),
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Heap {
+            objects: HashMap::new(),
+            allocations: 0,
+            threshold: MIN_THRESHOLD,
+            stress: false,
+            stats: GcStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> GcStats {
+        self.stats
+    }
+
+    /// In stress mode the heap collects every time it tracks an object, which shakes out objects
+    /// that are used without being reachable.
+    pub fn set_stress(&mut self, stress: bool) {
+        self.stress = stress;
+    }
+
+    /// Tracks the objects in `value` that aren't tracked yet, then collects if enough have been
+    /// tracked since the last collection.
+    pub fn track(&mut self, value: &Value) {
+        self.track_value(value);
+        self.collect_if_due();
+    }
+
+    /* the objects in a tracked object were tracked along with it, so the walk stops there */
+    fn track_value(&mut self, value: &Value) {
+        match value {
+            Value::List(list) => {
+                if self.insert(address(list), Tracked::List(Rc::downgrade(list))) {
+                    for element in list.borrow().iter() {
+                        self.track_value(element);
+                    }
+                }
+            }
+            Value::Map(map) => {
+                if self.insert(address(map), Tracked::Map(Rc::downgrade(map))) {
+                    for value in map.borrow().values() {
+                        self.track_value(value);
+                    }
+                }
+            }
+            /* a frame can only be part of a cycle once a closure has captured it, so frames are
+             * tracked from then on rather than on every call */
+            Value::Function(closure) => {
+                if self.insert(address(closure), Tracked::Closure(Rc::downgrade(closure))) {
+                    for frame in closure.scope().frames() {
+                        self.insert(address(frame), Tracked::Frame(Rc::downgrade(frame)));
+                    }
+                }
+            }
+            Value::Numeric(_)
+            | Value::String_(_)
+            | Value::Boolean(_)
+            | Value::NativeFunction(_)
+            | Value::Nil => (),
+        }
+    }
+
+    /* returns whether the object is newly tracked. the address of an object that has been freed
+     * may be reused by a new one, which then replaces it */
+    fn insert(&mut self, address: usize, tracked: Tracked) -> bool {
+        if self
+            .objects
+            .get(&address)
+            .is_some_and(|existing| existing.is_alive())
+        {
+            return false;
+        }
+
+        self.objects.insert(address, tracked);
+        self.allocations += 1;
+        true
+    }
+
+    fn collect_if_due(&mut self) {
+        if self.stress || self.allocations >= self.threshold {
+            self.collect();
+        }
+    }
+
+    /// Frees every tracked object that can't be reached from a root.
+    pub fn collect(&mut self) {
+        self.objects.retain(|_, tracked| tracked.is_alive());
+
+        let objects: Vec<Object> = self.objects.values().filter_map(Tracked::upgrade).collect();
+        let indices: HashMap<usize, usize> = objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| (object.address(), index))
+            .collect();
+
+        /* the references to each object, less the one held by `objects`, less those from other
+         * tracked objects, leaves those from outside: a root has some */
+        let mut external: Vec<usize> = objects
+            .iter()
+            .map(|object| object.strong_count() - 1)
+            .collect();
+
+        let children: Vec<Option<Vec<usize>>> = objects
+            .iter()
+            .map(|object| {
+                object.children().map(|children| {
+                    children
+                        .into_iter()
+                        .filter_map(|child| indices.get(&child).copied())
+                        .collect()
+                })
+            })
+            .collect();
+
+        for child in children.iter().flatten().flatten() {
+            external[*child] -= 1;
+        }
+
+        /* an object that is being modified can't be looked into, so it is kept along with
+         * everything it refers to */
+        let mut reachable = vec![false; objects.len()];
+        let mut pending: Vec<usize> = (0..objects.len())
+            .filter(|&index| external[index] > 0 || children[index].is_none())
+            .collect();
+
+        while let Some(index) = pending.pop() {
+            if std::mem::replace(&mut reachable[index], true) {
+                continue;
+            }
+
+            pending.extend(children[index].iter().flatten());
+        }
+
+        /* the contents are dropped once every object has been emptied, since dropping them frees
+         * other garbage */
+        let mut contents = Vec::new();
+        let mut freed = 0;
+
+        for (object, _) in objects
+            .iter()
+            .zip(&reachable)
+            .filter(|(_, reachable)| !**reachable)
+        {
+            self.objects.remove(&object.address());
+            self.stats.bytes_freed += object.empty(&mut contents);
+            freed += 1;
+        }
+
+        self.stats.collections += 1;
+        self.stats.objects_freed += freed;
+        self.allocations = 0;
+        self.threshold = MIN_THRESHOLD.max(2 * (objects.len() - freed));
+
+        drop(objects);
+        drop(contents);
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/* the tracked objects themselves would swamp the environment's debug output */
+impl std::fmt::Debug for Heap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Heap")
+            .field("tracked", &self.objects.len())
+            .field("stats", &self.stats)
+            .finish_non_exhaustive()
+    }
+}
+
+fn address<T>(shared: &Rc<T>) -> usize {
+    Rc::as_ptr(shared) as *const () as usize
+}
+
+impl Tracked {
+    fn is_alive(&self) -> bool {
+        match self {
+            Tracked::List(list) => list.strong_count() > 0,
+            Tracked::Map(map) => map.strong_count() > 0,
+            Tracked::Closure(closure) => closure.strong_count() > 0,
+            Tracked::Frame(frame) => frame.strong_count() > 0,
+        }
+    }
+
+    fn upgrade(&self) -> Option<Object> {
+        match self {
+            Tracked::List(list) => list.upgrade().map(Object::List),
+            Tracked::Map(map) => map.upgrade().map(Object::Map),
+            Tracked::Closure(closure) => closure.upgrade().map(Object::Closure),
+            Tracked::Frame(frame) => frame.upgrade().map(Object::Frame),
+        }
+    }
+}
+
+impl Object {
+    fn address(&self) -> usize {
+        match self {
+            Object::List(list) => address(list),
+            Object::Map(map) => address(map),
+            Object::Closure(closure) => address(closure),
+            Object::Frame(frame) => address(frame),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Object::List(list) => Rc::strong_count(list),
+            Object::Map(map) => Rc::strong_count(map),
+            Object::Closure(closure) => Rc::strong_count(closure),
+            Object::Frame(frame) => Rc::strong_count(frame),
+        }
+    }
+
+    /* the addresses of the objects this one refers to, once for each reference, or None if it
+     * is borrowed mutably */
+    fn children(&self) -> Option<Vec<usize>> {
+        let mut children = Vec::new();
+
+        match self {
+            Object::List(list) => values(list.try_borrow().ok()?.iter(), &mut children),
+            Object::Map(map) => values(map.try_borrow().ok()?.values(), &mut children),
+            Object::Closure(closure) => {
+                children.extend(closure.scope().frames().iter().map(address))
+            }
+            Object::Frame(frame) => values(&frame.try_borrow().ok()?.values, &mut children),
+        }
+
+        Some(children)
+    }
+
+    /* moves the object's contents into `contents`, returning roughly how much memory the object
+     * took up. closures have nothing to empty: every cycle runs through a list, map or frame */
+    fn empty(&self, contents: &mut Vec<Value>) -> usize {
+        /* nested objects are counted as they are emptied themselves, and may be borrowed here */
+        let bytes = |values: &[Value]| {
+            values
+                .iter()
+                .map(|value| match value {
+                    Value::String_(string) => std::mem::size_of::<Value>() + string.len(),
+                    _ => std::mem::size_of::<Value>(),
+                })
+                .sum::<usize>()
+        };
+
+        match self {
+            Object::List(list) => match list.try_borrow_mut() {
+                Ok(mut list) => {
+                    let size = std::mem::size_of::<RefCell<Vec<Value>>>() + bytes(&list);
+                    contents.append(&mut list);
+                    size
+                }
+                Err(_) => 0,
+            },
+            Object::Map(map) => match map.try_borrow_mut() {
+                Ok(mut map) => {
+                    let keys: usize = map.keys().map(MapKey::allocation).sum();
+                    let size = std::mem::size_of::<RefCell<IndexMap<MapKey, Value>>>() + keys;
+
+                    let values: Vec<Value> = map.drain(..).map(|(_, value)| value).collect();
+                    let size = size + bytes(&values);
+                    contents.extend(values);
+                    size
+                }
+                Err(_) => 0,
+            },
+            Object::Closure(_) => std::mem::size_of::<Closure>(),
+            Object::Frame(frame) => match frame.try_borrow_mut() {
+                Ok(mut frame) => {
+                    let size = std::mem::size_of::<RefCell<Frame>>() + bytes(&frame.values);
+                    contents.append(&mut frame.values);
+                    size
+                }
+                Err(_) => 0,
+            },
+        }
+    }
+}
+
+fn values<'a>(values: impl IntoIterator<Item = &'a Value>, children: &mut Vec<usize>) {
+    for value in values {
+        match value {
+            Value::List(list) => children.push(address(list)),
+            Value::Map(map) => children.push(address(map)),
+            Value::Function(closure) => children.push(address(closure)),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::{Evaluator, Value};
+    use crate::output::OutputBuffer;
+    use crate::test_util::run;
+
+    use std::rc::Rc;
+
+    /* whether the value in global `name` is freed once the global no longer refers to it */
+    fn freed_when_dropped(evaluator: &mut Evaluator, name: &str) -> bool {
+        let weak = match evaluator.global(name).unwrap() {
+            Value::List(list) => Rc::downgrade(&list) as std::rc::Weak<dyn std::any::Any>,
+            Value::Map(map) => Rc::downgrade(&map) as std::rc::Weak<dyn std::any::Any>,
+            Value::Function(closure) => Rc::downgrade(&closure) as std::rc::Weak<dyn std::any::Any>,
+            value => panic!("expected a heap value, found {value:?}"),
+        };
+
+        evaluator.set_global(name, Value::Nil);
+        evaluator.collect_garbage();
+
+        weak.upgrade().is_none()
+    }
+
+    #[test]
+    fn test_collects_cycles() {
+        let mut evaluator = Evaluator::new();
+        run(
+            &mut evaluator,
+            "var list = []; push(list, list);
+            var map = {}; map[\"self\"] = [map];
+            fun outer() { fun inner() { return inner; } return inner; }
+            var closure = outer();",
+        )
+        .unwrap();
+
+        let before = evaluator.gc_stats();
+
+        assert!(freed_when_dropped(&mut evaluator, "list"));
+        assert!(freed_when_dropped(&mut evaluator, "map"));
+        assert!(freed_when_dropped(&mut evaluator, "closure"));
+
+        let after = evaluator.gc_stats();
+        assert_eq!(after.collections, before.collections + 3);
+        assert!(after.objects_freed >= before.objects_freed + 5);
+        assert!(after.bytes_freed > before.bytes_freed);
+    }
+
+    #[test]
+    fn test_keeps_reachable_values() {
+        let mut evaluator = Evaluator::new();
+        let output = OutputBuffer::new();
+        evaluator.set_output(output.clone());
+
+        run(
+            &mut evaluator,
+            "var list = [[1]]; push(list, list);
+            fun counter() { var count = 0; fun increment() { count += 1; return count; } return increment; }
+            var next = counter();",
+        )
+        .unwrap();
+
+        evaluator.collect_garbage();
+        run(
+            &mut evaluator,
+            "next(); print next(); print list[0]; print len(list[1]);",
+        )
+        .unwrap();
+
+        assert_eq!(output.contents(), "2\n[1]\n2\n");
+        assert_eq!(evaluator.gc_stats().objects_freed, 0);
+    }
+
+    #[test]
+    fn test_collects_on_its_own() {
+        let mut evaluator = Evaluator::new();
+        run(
+            &mut evaluator,
+            "for (var i = 0; i < 5000; i += 1) { var list = []; push(list, list); }",
+        )
+        .unwrap();
+
+        let stats = evaluator.gc_stats();
+        assert!(stats.collections > 0);
+        assert!(stats.objects_freed > 0);
+    }
+
+    /* every allocation collects, so a value used without being reachable would be emptied */
+    #[test]
+    fn test_stress() {
+        let mut evaluator = Evaluator::new();
+        let output = OutputBuffer::new();
+        evaluator.set_output(output.clone());
+        evaluator.set_gc_stress(true);
+
+        run(
+            &mut evaluator,
+            "fun pair(a, b) { return [a, b]; }
+            fun adder(n) { return (x) => x + n; }
+            var nested = [pair([1], {\"a\": [2]}), pair(adder(3), [[4]])];
+            { var cycle = [nested]; push(cycle, cycle); }
+            print nested[0];
+            print nested[1][0](4);
+            print [keys({\"x\": [1]}), {\"k\": [adder(1)(1)]}];",
+        )
+        .unwrap();
+
+        assert_eq!(
+            output.contents(),
+            "[[1], {\"a\": [2]}]\n7\n[[\"x\"], {\"k\": [2]}]\n"
+        );
+        assert!(evaluator.gc_stats().objects_freed > 0);
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Stops a running evaluation from another thread or a signal handler. The evaluation notices at
+/// the next loop iteration or function call and fails with `RuntimeError::Interrupted`. Clones
+/// share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+
+    /// Forgets an interrupt that arrived while nothing was running.
+    pub fn clear(&self) {
+        self.interrupted.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether an interrupt has arrived since the last call, which forgets it.
+    pub fn take(&self) -> bool {
+        self.interrupted.swap(false, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::{Arity, Evaluator, RuntimeError, Value};
+    use crate::test_util::run;
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_interrupt_loops() {
+        for code in ["while (true) {}", "for (var i = 0; true; i = i + 1) {}"] {
+            let mut evaluator = Evaluator::new();
+            let handle = evaluator.interrupt_handle();
+            let done = Arc::new(AtomicBool::new(false));
+
+            /* an interrupt that lands before the evaluation starts is forgotten, so keep
+             * interrupting until it has returned */
+            let interrupter = std::thread::spawn({
+                let done = done.clone();
+                move || {
+                    while !done.load(Ordering::Relaxed) {
+                        handle.interrupt();
+                        std::thread::sleep(std::time::Duration::from_millis(1));
+                    }
+                }
+            });
+
+            let result = run(&mut evaluator, code);
+            done.store(true, Ordering::Relaxed);
+            interrupter.join().unwrap();
+
+            assert!(matches!(result, Err(RuntimeError::Interrupted)));
+
+            assert_eq!(evaluator.environment.depth(), 0);
+            run(&mut evaluator, "var after = 1;").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_interrupt_calls() {
+        let mut evaluator = Evaluator::new();
+        let handle = evaluator.interrupt_handle();
+
+        evaluator
+            .define_native("stop", Arity::Exactly(0), move |_, _| {
+                handle.interrupt();
+                Ok(Value::Nil)
+            })
+            .unwrap();
+
+        assert!(matches!(
+            run(
+                &mut evaluator,
+                "var called = false; fun f() { called = true; } stop(); f();"
+            ),
+            Err(RuntimeError::Interrupted)
+        ));
+        assert!(matches!(
+            evaluator.global("called"),
+            Some(Value::Boolean(false))
+        ));
+    }
+
+    #[test]
+    fn test_interrupt_before_evaluation() {
+        let mut evaluator = Evaluator::new();
+
+        evaluator.interrupt_handle().interrupt();
+
+        assert!(run(&mut evaluator, "for (var i = 0; i < 10; i = i + 1) {}").is_ok());
+    }
+}
@@ -1,4 +1,22 @@
-mod evaluator;
+mod clock;
 mod environment;
+mod evaluator;
+mod heap;
+mod interrupt;
+mod limits;
+mod native;
+mod output;
+mod system;
+#[cfg(test)]
+mod test_util;
 
-pub use evaluator::Evaluator;
+pub use clock::{ManualClock, SystemClock, TimeSource};
+pub use evaluator::{
+    Arity, Context, Evaluator, EvaluatorResult, NativeFn, NativeFunction, RuntimeError, Value,
+};
+pub use heap::GcStats;
+pub use interrupt::InterruptHandle;
+pub use limits::{Limits, Usage};
+pub use native::{Host, NativeValue, Natives, standard_library};
+pub use output::OutputBuffer;
+pub use system::System;
@@ -0,0 +1,166 @@
+use std::time::{Duration, Instant};
+
+/// Bounds on the work a script may do, for hosts that run untrusted code. Each call to
+/// `Evaluator::evaluate`, `Evaluator::call` or `Vm::run` gets the full budget again, so an engine
+/// that hit a limit can go on to run other scripts. Every limit but the call depth is off by default;
+/// that one defaults to `DEFAULT_MAX_CALL_DEPTH`, so that runaway recursion fails with
+/// `CallDepthExceeded` instead of overflowing the stack.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// The number of statements that may be executed, or of instructions on the virtual machine.
+    pub max_steps: Option<u64>,
+    /// How long, in wall-clock time, the evaluation may take.
+    pub timeout: Option<Duration>,
+    /// How deeply calls of lox functions may nest.
+    pub max_call_depth: Option<usize>,
+    /// Roughly how many bytes the values reachable from the script may take up.
+    pub max_memory: Option<usize>,
+}
+
+impl Limits {
+    /// Deep enough for any reasonable recursion. A thousand calls need about 4 MiB of stack in a
+    /// release build and 16 MiB in a debug build, so hosts that evaluate on a thread with a
+    /// smaller stack should lower the limit.
+    pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            timeout: None,
+            max_call_depth: Some(Self::DEFAULT_MAX_CALL_DEPTH),
+            max_memory: None,
+        }
+    }
+}
+
+/// How much of the limits the current run has used up.
+#[derive(Debug, Default)]
+pub struct Usage {
+    pub steps: u64,
+    pub deadline: Option<Instant>,
+    /// The memory in use the last time it was measured, plus everything allocated since. This
+    /// overestimates, as it ignores whatever has been freed, so it is only measured again once
+    /// the estimate goes over the limit.
+    pub memory: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::{Evaluator, RuntimeError};
+    use crate::test_util::run;
+
+    fn limited(limits: Limits) -> Evaluator {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_limits(limits);
+
+        evaluator
+    }
+
+    /* a limited evaluator must still run well-behaved scripts, and from a clean scope */
+    fn assert_reusable(evaluator: &mut Evaluator) {
+        assert_eq!(evaluator.environment.depth(), 0);
+        run(
+            evaluator,
+            "fun reusable() { return 1; } var reused = reusable();",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut evaluator = limited(Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        });
+
+        assert!(matches!(
+            run(&mut evaluator, "while (true) {}"),
+            Err(RuntimeError::StepLimitExceeded)
+        ));
+        assert_reusable(&mut evaluator);
+
+        /* the budget is per evaluation rather than shared by all of them */
+        for _ in 0..2 {
+            run(&mut evaluator, "for (var i = 0; i < 300; i = i + 1) { i; }").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut evaluator = limited(Limits {
+            timeout: Some(Duration::from_millis(20)),
+            ..Limits::default()
+        });
+
+        assert!(matches!(
+            run(&mut evaluator, "{ while (true) {} }"),
+            Err(RuntimeError::Timeout)
+        ));
+        assert_reusable(&mut evaluator);
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        let mut evaluator = limited(Limits {
+            max_call_depth: Some(50),
+            ..Limits::default()
+        });
+
+        assert!(matches!(
+            run(&mut evaluator, "fun f(n) { return f(n + 1); } f(0);"),
+            Err(RuntimeError::CallDepthExceeded)
+        ));
+        assert_reusable(&mut evaluator);
+
+        run(
+            &mut evaluator,
+            "fun g(n) { if (n > 0) return g(n - 1); return n; } g(49);",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_default_max_call_depth() {
+        let result = std::thread::Builder::new()
+            .stack_size(32 * 1024 * 1024)
+            .spawn(|| run(&mut Evaluator::new(), "fun f() { return f(); } f();"))
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert!(matches!(result, Err(RuntimeError::CallDepthExceeded)));
+    }
+
+    #[test]
+    fn test_max_memory() {
+        let limits = Limits {
+            max_memory: Some(64 * 1024),
+            ..Limits::default()
+        };
+
+        for code in [
+            "var s = \"ab\"; while (true) s = s + s;",
+            "var l = []; while (true) push(l, [1, 2, 3]);",
+            "var m = {}; var i = 0; while (true) { m[i] = i; i = i + 1; }",
+        ] {
+            let mut evaluator = limited(limits);
+
+            assert!(matches!(
+                run(&mut evaluator, code),
+                Err(RuntimeError::MemoryLimitExceeded)
+            ));
+            assert_reusable(&mut evaluator);
+        }
+
+        /* memory that has been freed again doesn't count */
+        let mut evaluator = limited(limits);
+        run(
+            &mut evaluator,
+            "var s; for (var i = 0; i < 10000; i = i + 1) { s = \"abcdefghij\" + \"klmnopqrst\"; }",
+        )
+        .unwrap();
+    }
+}
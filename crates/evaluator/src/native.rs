@@ -0,0 +1,140 @@
+pub mod io;
+pub mod math;
+pub mod string;
+pub mod time;
+
+use crate::evaluator::{Arity, Context, EvaluatorResult, RuntimeError};
+use crate::system::System;
+
+use indexmap::IndexMap;
+
+use std::cell::RefCell;
+use std::hash::Hash;
+
+/// What the standard library needs of the interpreter that calls it. The evaluator's `Context`
+/// is one, and other engines implement it to share the standard library with the evaluator.
+pub trait Host {
+    fn system(&mut self) -> &mut System;
+
+    /// Counts memory a native allocates against the memory limit, see `Context::allocate`.
+    fn allocate(&mut self, bytes: usize) -> EvaluatorResult<()>;
+}
+
+/// A value the standard library can take and return, so that engines with values of their own
+/// can share it.
+pub trait NativeValue:
+    'static + Clone + std::fmt::Display + From<f64> + From<bool> + From<String> + From<Self::Key>
+{
+    /// The values that may key a map.
+    type Key: TryFrom<Self, Error = RuntimeError> + Clone + Eq + Hash;
+
+    fn nil() -> Self;
+    fn list(elements: Vec<Self>) -> Self;
+
+    fn as_number(&self) -> Option<f64>;
+    fn as_str(&self) -> Option<&str>;
+    fn as_list(&self) -> Option<&RefCell<Vec<Self>>>;
+    fn as_map(&self) -> Option<&RefCell<IndexMap<Self::Key, Self>>>;
+}
+
+/// Where the standard library defines its natives and constants.
+pub trait Natives<V: NativeValue> {
+    fn native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut dyn Host, &[V]) -> EvaluatorResult<V> + 'static,
+    );
+
+    /// Defines a global that scripts can read but not assign to.
+    fn constant(&mut self, name: &str, value: V);
+}
+
+impl Host for Context<'_> {
+    fn system(&mut self) -> &mut System {
+        Context::system(self)
+    }
+
+    fn allocate(&mut self, bytes: usize) -> EvaluatorResult<()> {
+        Context::allocate(self, bytes)
+    }
+}
+
+/// Defines every native and constant of the standard library.
+pub fn standard_library<V: NativeValue>(natives: &mut impl Natives<V>) {
+    natives.native("len", Arity::Exactly(1), len);
+    natives.native("push", Arity::Exactly(2), push);
+    natives.native("pop", Arity::Exactly(1), pop);
+    natives.native("keys", Arity::Exactly(1), keys);
+    natives.native("values", Arity::Exactly(1), values);
+    natives.native("has", Arity::Exactly(2), has);
+    natives.native("remove", Arity::Exactly(2), remove);
+
+    math::register(natives);
+    string::register(natives);
+    io::register(natives);
+    time::register(natives);
+}
+
+fn as_list<V: NativeValue>(value: &V) -> EvaluatorResult<&RefCell<Vec<V>>> {
+    value.as_list().ok_or(RuntimeError::TypeError)
+}
+
+fn as_map<V: NativeValue>(value: &V) -> EvaluatorResult<&RefCell<IndexMap<V::Key, V>>> {
+    value.as_map().ok_or(RuntimeError::TypeError)
+}
+
+fn len<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let value = &arguments[0];
+
+    let length = match (value.as_list(), value.as_map(), value.as_str()) {
+        (Some(list), _, _) => list.borrow().len(),
+        (_, Some(map), _) => map.borrow().len(),
+        (_, _, Some(string)) => string.chars().count(),
+        _ => return Err(RuntimeError::TypeError),
+    };
+
+    Ok(V::from(length as f64))
+}
+
+fn push<V: NativeValue>(host: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let list = as_list(&arguments[0])?;
+
+    host.allocate(std::mem::size_of::<V>())?;
+    list.borrow_mut().push(arguments[1].clone());
+    Ok(V::nil())
+}
+
+/* popping an empty list yields nil rather than an error */
+fn pop<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let list = as_list(&arguments[0])?;
+
+    Ok(list.borrow_mut().pop().unwrap_or_else(V::nil))
+}
+
+fn keys<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let map = as_map(&arguments[0])?;
+
+    Ok(V::list(map.borrow().keys().cloned().map(V::from).collect()))
+}
+
+fn values<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let map = as_map(&arguments[0])?;
+
+    Ok(V::list(map.borrow().values().cloned().collect()))
+}
+
+fn has<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let map = as_map(&arguments[0])?;
+
+    let key = V::Key::try_from(arguments[1].clone())?;
+    Ok(V::from(map.borrow().contains_key(&key)))
+}
+
+/* removing a missing key yields nil rather than an error */
+fn remove<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let map = as_map(&arguments[0])?;
+
+    let key = V::Key::try_from(arguments[1].clone())?;
+    Ok(map.borrow_mut().shift_remove(&key).unwrap_or_else(V::nil))
+}
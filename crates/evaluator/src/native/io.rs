@@ -0,0 +1,213 @@
+use crate::evaluator::{Arity, EvaluatorResult, RuntimeError};
+use crate::native::{Host, NativeValue, Natives};
+
+use std::io::Write;
+
+pub fn register<V: NativeValue>(natives: &mut impl Natives<V>) {
+    natives.native("readLine", Arity::Exactly(0), read_line);
+    natives.native("eprint", Arity::Exactly(1), eprint);
+    natives.native("readFile", Arity::Exactly(1), read_file);
+    natives.native("writeFile", Arity::Exactly(2), write_file);
+    natives.native("appendFile", Arity::Exactly(2), append_file);
+    natives.native("exists", Arity::Exactly(1), exists);
+}
+
+fn as_string<V: NativeValue>(value: &V) -> EvaluatorResult<&str> {
+    value.as_str().ok_or(RuntimeError::TypeError)
+}
+
+/* the path argument of a filesystem native, once the embedder is known to allow it */
+fn path<'a, V: NativeValue>(host: &mut dyn Host, value: &'a V) -> EvaluatorResult<&'a str> {
+    if !host.system().filesystem_access() {
+        return Err(RuntimeError::FilesystemAccessDenied);
+    }
+
+    as_string(value)
+}
+
+/* the console natives need the embedder's permission just like the filesystem ones */
+fn console(host: &mut dyn Host) -> EvaluatorResult<()> {
+    if !host.system().console_access() {
+        return Err(RuntimeError::ConsoleAccessDenied);
+    }
+
+    Ok(())
+}
+
+/* a line of input without its line ending, or nil at the end of input */
+fn read_line<V: NativeValue>(host: &mut dyn Host, _: &[V]) -> EvaluatorResult<V> {
+    console(host)?;
+    let mut line = String::new();
+
+    if host
+        .system()
+        .read_line(&mut line)
+        .map_err(RuntimeError::Io)?
+        == 0
+    {
+        return Ok(V::nil());
+    }
+
+    let length = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(length);
+
+    Ok(V::from(line))
+}
+
+fn eprint<V: NativeValue>(host: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    console(host)?;
+
+    writeln!(host.system().error_output(), "{}", arguments[0]).map_err(RuntimeError::Io)?;
+    Ok(V::nil())
+}
+
+fn read_file<V: NativeValue>(host: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let path = path(host, &arguments[0])?;
+
+    Ok(V::from(
+        std::fs::read_to_string(path).map_err(RuntimeError::Io)?,
+    ))
+}
+
+fn write_file<V: NativeValue>(host: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let path = path(host, &arguments[0])?;
+
+    std::fs::write(path, as_string(&arguments[1])?).map_err(RuntimeError::Io)?;
+    Ok(V::nil())
+}
+
+fn append_file<V: NativeValue>(host: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let path = path(host, &arguments[0])?;
+    let contents = as_string(&arguments[1])?;
+
+    std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(RuntimeError::Io)?;
+    Ok(V::nil())
+}
+
+fn exists<V: NativeValue>(host: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let path = path(host, &arguments[0])?;
+
+    Ok(V::from(std::fs::exists(path).map_err(RuntimeError::Io)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::{Evaluator, RuntimeError, Value};
+    use crate::output::OutputBuffer;
+    use crate::test_util::run;
+
+    /* a path in the temporary directory that no other test uses */
+    fn temporary_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("lox-io-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_files() {
+        let mut evaluator = Evaluator::new();
+        let path = temporary_path("files");
+        evaluator.set_global("path", Value::from(path.as_str()));
+
+        run(
+            &mut evaluator,
+            "var before = exists(path);
+             writeFile(path, \"a\");
+             appendFile(path, \"b\");
+             var contents = readFile(path);
+             var after = exists(path);",
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            evaluator.global("before"),
+            Some(Value::Boolean(false))
+        ));
+        assert!(matches!(
+            evaluator.global("after"),
+            Some(Value::Boolean(true))
+        ));
+        assert!(matches!(evaluator.global("contents"), Some(Value::String_(s)) if &*s == "ab"));
+    }
+
+    #[test]
+    fn test_io_error() {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_global("path", Value::from(temporary_path("missing").as_str()));
+
+        assert!(matches!(
+            run(&mut evaluator, "readFile(path);"),
+            Err(RuntimeError::Io(_))
+        ));
+        assert!(matches!(
+            run(&mut evaluator, "writeFile(1, \"a\");"),
+            Err(RuntimeError::TypeError)
+        ));
+    }
+
+    #[test]
+    fn test_filesystem_access_denied() {
+        let mut evaluator = Evaluator::new();
+        let path = temporary_path("denied");
+        evaluator.set_global("path", Value::from(path.as_str()));
+        evaluator.set_filesystem_access(false);
+
+        for code in [
+            "readFile(path);",
+            "writeFile(path, \"a\");",
+            "appendFile(path, \"a\");",
+            "exists(path);",
+        ] {
+            assert!(matches!(
+                run(&mut evaluator, code),
+                Err(RuntimeError::FilesystemAccessDenied)
+            ));
+        }
+
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_console() {
+        let mut evaluator = Evaluator::new();
+        let errors = OutputBuffer::new();
+        evaluator.set_error_output(errors.clone());
+        evaluator.set_input(std::io::Cursor::new("first\r\nsecond"));
+
+        run(
+            &mut evaluator,
+            "var lines = [readLine(), readLine(), readLine()]; eprint(lines);",
+        )
+        .unwrap();
+
+        assert_eq!(errors.contents(), "[\"first\", \"second\", nil]\n");
+    }
+
+    #[test]
+    fn test_console_access_denied() {
+        let mut evaluator = Evaluator::new();
+        let errors = OutputBuffer::new();
+        evaluator.set_error_output(errors.clone());
+        evaluator.set_input(std::io::Cursor::new("line"));
+        evaluator.set_console_access(false);
+
+        for code in ["readLine();", "eprint(\"a\");"] {
+            assert!(matches!(
+                run(&mut evaluator, code),
+                Err(RuntimeError::ConsoleAccessDenied)
+            ));
+        }
+
+        assert_eq!(errors.contents(), "");
+        evaluator.set_console_access(true);
+        run(&mut evaluator, "var line = readLine();").unwrap();
+        assert!(matches!(evaluator.global("line"), Some(Value::String_(s)) if &*s == "line"));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::evaluator::{Arity, EvaluatorResult, RuntimeError};
+use crate::native::{Host, NativeValue, Natives};
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::SystemTime;
+
+pub fn register<V: NativeValue>(natives: &mut impl Natives<V>) {
+    natives.constant("PI", V::from(std::f64::consts::PI));
+    natives.constant("INFINITY", V::from(f64::INFINITY));
+
+    natives.native("sqrt", Arity::Exactly(1), unary(f64::sqrt));
+    natives.native("abs", Arity::Exactly(1), unary(f64::abs));
+    natives.native("floor", Arity::Exactly(1), unary(f64::floor));
+    natives.native("ceil", Arity::Exactly(1), unary(f64::ceil));
+    natives.native("round", Arity::Exactly(1), unary(f64::round));
+    natives.native("sin", Arity::Exactly(1), unary(f64::sin));
+    natives.native("cos", Arity::Exactly(1), unary(f64::cos));
+    natives.native("log", Arity::Exactly(1), unary(f64::ln));
+    natives.native("pow", Arity::Exactly(2), pow);
+    natives.native("min", Arity::AtLeast(1), min);
+    natives.native("max", Arity::AtLeast(1), max);
+
+    /* random and seed share the generator's state */
+    let state = Rc::new(Cell::new(initial_seed()));
+
+    let random_state = state.clone();
+    natives.native("random", Arity::Exactly(0), move |_, _| {
+        Ok(V::from(random(&random_state)))
+    });
+    natives.native("seed", Arity::Exactly(1), move |_, arguments| {
+        state.set(number(&arguments[0])?.to_bits());
+        Ok(V::nil())
+    });
+}
+
+fn number<V: NativeValue>(value: &V) -> EvaluatorResult<f64> {
+    value.as_number().ok_or(RuntimeError::TypeError)
+}
+
+fn unary<V: NativeValue>(
+    function: fn(f64) -> f64,
+) -> impl Fn(&mut dyn Host, &[V]) -> EvaluatorResult<V> + 'static {
+    move |_, arguments| Ok(V::from(function(number(&arguments[0])?)))
+}
+
+fn pow<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let base = number(&arguments[0])?;
+    let exponent = number(&arguments[1])?;
+
+    Ok(V::from(base.powf(exponent)))
+}
+
+fn min<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let mut min = number(&arguments[0])?;
+
+    for argument in &arguments[1..] {
+        min = min.min(number(argument)?);
+    }
+
+    Ok(V::from(min))
+}
+
+fn max<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let mut max = number(&arguments[0])?;
+
+    for argument in &arguments[1..] {
+        max = max.max(number(argument)?);
+    }
+
+    Ok(V::from(max))
+}
+
+fn initial_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+/* splitmix64, which is small and good enough for scripts; not suitable for anything secret */
+fn random(state: &Cell<u64>) -> f64 {
+    let seed = state.get().wrapping_add(0x9e3779b97f4a7c15);
+    state.set(seed);
+
+    let mut z = seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+
+    /* the top 53 bits fill the mantissa of a double in [0, 1) */
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::RuntimeError;
+    use crate::test_util::{evaluate, evaluate_number, result_of};
+
+    #[test]
+    fn test_functions() {
+        assert_eq!(evaluate_number("sqrt(16)"), 4.0);
+        assert_eq!(evaluate_number("pow(2, 10)"), 1024.0);
+        assert_eq!(evaluate_number("abs(0 - 3)"), 3.0);
+        assert_eq!(evaluate_number("floor(1.5)"), 1.0);
+        assert_eq!(evaluate_number("ceil(1.5)"), 2.0);
+        assert_eq!(evaluate_number("round(2.5)"), 3.0);
+        assert_eq!(evaluate_number("round(0 - 2.5)"), -3.0);
+        assert_eq!(evaluate_number("min(3, 1, 2)"), 1.0);
+        assert_eq!(evaluate_number("max(3, 1, 2)"), 3.0);
+        assert_eq!(evaluate_number("max(7)"), 7.0);
+        assert_eq!(evaluate_number("sin(0)"), 0.0);
+        assert_eq!(evaluate_number("cos(0)"), 1.0);
+        assert_eq!(evaluate_number("log(1)"), 0.0);
+        assert!(evaluate_number("sqrt(0 - 1)").is_nan());
+    }
+
+    #[test]
+    fn test_constants() {
+        assert_eq!(evaluate_number("PI"), std::f64::consts::PI);
+        assert_eq!(evaluate_number("INFINITY"), f64::INFINITY);
+        assert_eq!(evaluate_number("0 - INFINITY"), f64::NEG_INFINITY);
+
+        for code in ["PI = 3", "INFINITY += 1", "PI++"] {
+            assert!(
+                matches!(evaluate(code), Err(RuntimeError::ConstantAssignment)),
+                "{code} should fail"
+            );
+        }
+
+        assert!(matches!(
+            result_of("var PI = 3;"),
+            Err(RuntimeError::VariableRedefinition)
+        ));
+        assert_eq!(
+            evaluate_number("(fun () { var PI = 3; return PI; })()"),
+            3.0
+        );
+    }
+
+    #[test]
+    fn test_type_errors() {
+        for call in ["sqrt(\"4\")", "pow(2, nil)", "min(1, true)", "seed([])"] {
+            assert!(
+                matches!(evaluate(call), Err(RuntimeError::TypeError)),
+                "{call} should raise a type error"
+            );
+        }
+
+        assert!(matches!(
+            evaluate("min()"),
+            Err(RuntimeError::WrongNumberOfArguments)
+        ));
+    }
+
+    #[test]
+    fn test_random() {
+        let first = evaluate("[seed(42), random(), random()][1]").unwrap();
+        let second = evaluate("[seed(42), random(), random()][1]").unwrap();
+        assert_eq!(first.to_string(), second.to_string());
+
+        for _ in 0..100 {
+            let value = evaluate_number("random()");
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use crate::evaluator::{Arity, EvaluatorResult, RuntimeError};
+use crate::native::{Host, NativeValue, Natives};
+
+/* strings are indexed by unicode scalar values, the same unit `len` counts in */
+pub fn register<V: NativeValue>(natives: &mut impl Natives<V>) {
+    natives.native("substr", Arity::Exactly(3), substr);
+    natives.native("indexOf", Arity::Exactly(2), index_of);
+    natives.native("split", Arity::Exactly(2), split);
+    natives.native("join", Arity::Exactly(2), join);
+    natives.native("upper", Arity::Exactly(1), upper);
+    natives.native("lower", Arity::Exactly(1), lower);
+    natives.native("trim", Arity::Exactly(1), trim);
+    natives.native("replace", Arity::Exactly(3), replace);
+    natives.native("startsWith", Arity::Exactly(2), starts_with);
+    natives.native("chr", Arity::Exactly(1), chr);
+    natives.native("ord", Arity::Exactly(1), ord);
+    natives.native("str", Arity::Exactly(1), str);
+    natives.native("num", Arity::Exactly(1), num);
+}
+
+fn as_string<V: NativeValue>(value: &V) -> EvaluatorResult<&str> {
+    value.as_str().ok_or(RuntimeError::TypeError)
+}
+
+/* a whole number no larger than `limit` */
+fn position<V: NativeValue>(value: &V, limit: usize) -> EvaluatorResult<usize> {
+    let value = value.as_number().ok_or(RuntimeError::TypeError)?;
+
+    if value.fract() != 0.0 {
+        return Err(RuntimeError::InvalidIndex);
+    }
+
+    if value < 0.0 || value > limit as f64 {
+        return Err(RuntimeError::IndexOutOfBounds);
+    }
+
+    Ok(value as usize)
+}
+
+/// `substr(s, start, length)`, which must lie within the string.
+fn substr<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let string = as_string(&arguments[0])?;
+    let count = string.chars().count();
+
+    let start = position(&arguments[1], count)?;
+    let length = position(&arguments[2], count - start)?;
+
+    Ok(V::from(
+        string.chars().skip(start).take(length).collect::<String>(),
+    ))
+}
+
+/// The position of the first occurrence of the needle, or -1.
+fn index_of<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let string = as_string(&arguments[0])?;
+    let needle = as_string(&arguments[1])?;
+
+    let index = match string.find(needle) {
+        Some(byte_index) => string[..byte_index].chars().count() as f64,
+        None => -1.0,
+    };
+
+    Ok(V::from(index))
+}
+
+/* an empty separator splits the string into its characters */
+fn split<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let string = as_string(&arguments[0])?;
+    let separator = as_string(&arguments[1])?;
+
+    let parts: Vec<V> = match separator {
+        "" => string.chars().map(|c| V::from(c.to_string())).collect(),
+        _ => string
+            .split(separator)
+            .map(|part| V::from(String::from(part)))
+            .collect(),
+    };
+
+    Ok(V::list(parts))
+}
+
+/* elements that aren't strings are joined as `str` would print them */
+fn join<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let list = arguments[0].as_list().ok_or(RuntimeError::TypeError)?;
+    let separator = as_string(&arguments[1])?;
+
+    let parts: Vec<String> = list.borrow().iter().map(V::to_string).collect();
+    Ok(V::from(parts.join(separator)))
+}
+
+fn upper<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    Ok(V::from(as_string(&arguments[0])?.to_uppercase()))
+}
+
+fn lower<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    Ok(V::from(as_string(&arguments[0])?.to_lowercase()))
+}
+
+fn trim<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    Ok(V::from(String::from(as_string(&arguments[0])?.trim())))
+}
+
+/// Replaces every occurrence of the pattern.
+fn replace<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let string = as_string(&arguments[0])?;
+    let pattern = as_string(&arguments[1])?;
+    let replacement = as_string(&arguments[2])?;
+
+    if pattern.is_empty() {
+        return Err(RuntimeError::InvalidArgument);
+    }
+
+    Ok(V::from(string.replace(pattern, replacement)))
+}
+
+fn starts_with<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let string = as_string(&arguments[0])?;
+    let prefix = as_string(&arguments[1])?;
+
+    Ok(V::from(string.starts_with(prefix)))
+}
+
+/// The character with the given code point.
+fn chr<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let code = position(&arguments[0], char::MAX as usize)?;
+
+    match char::from_u32(code as u32) {
+        Some(c) => Ok(V::from(c.to_string())),
+        None => Err(RuntimeError::InvalidArgument),
+    }
+}
+
+/// The code point of a string holding exactly one character.
+fn ord<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let mut chars = as_string(&arguments[0])?.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(V::from(c as u32 as f64)),
+        _ => Err(RuntimeError::InvalidArgument),
+    }
+}
+
+fn str<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    Ok(V::from(arguments[0].to_string()))
+}
+
+/// Parses a number written the way a numeric literal is, optionally negated. Anything else,
+/// including surrounding whitespace, is an invalid argument.
+fn num<V: NativeValue>(_: &mut dyn Host, arguments: &[V]) -> EvaluatorResult<V> {
+    let string = as_string(&arguments[0])?;
+    let unsigned = string.strip_prefix('-').unwrap_or(string);
+
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let valid = match unsigned.split_once('.') {
+        Some((whole, fraction)) => is_digits(whole) && is_digits(fraction),
+        None => is_digits(unsigned),
+    };
+
+    match valid {
+        true => Ok(V::from(string.parse::<f64>().unwrap())),
+        false => Err(RuntimeError::InvalidArgument),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::{RuntimeError, Value};
+    use crate::test_util::{evaluate, evaluate_string};
+
+    #[test]
+    fn test_substr() {
+        assert_eq!(evaluate_string("substr(\"hello\", 1, 3)"), "ell");
+        assert_eq!(evaluate_string("substr(\"héllo\", 1, 1)"), "é");
+        assert_eq!(evaluate_string("substr(\"abc\", 3, 0)"), "");
+        assert!(matches!(
+            evaluate("substr(\"abc\", 2, 2)"),
+            Err(RuntimeError::IndexOutOfBounds)
+        ));
+        assert!(matches!(
+            evaluate("substr(\"abc\", 0.5, 1)"),
+            Err(RuntimeError::InvalidIndex)
+        ));
+    }
+
+    #[test]
+    fn test_index_of() {
+        assert!(matches!(
+            evaluate("indexOf(\"naïve café\", \"café\")"),
+            Ok(Value::Numeric(6.0))
+        ));
+        assert!(matches!(
+            evaluate("indexOf(\"abc\", \"d\")"),
+            Ok(Value::Numeric(-1.0))
+        ));
+    }
+
+    #[test]
+    fn test_split_and_join() {
+        assert_eq!(
+            evaluate("split(\"a,b,,c\", \",\")").unwrap().to_string(),
+            "[\"a\", \"b\", \"\", \"c\"]"
+        );
+        assert_eq!(
+            evaluate("split(\"añb\", \"\")").unwrap().to_string(),
+            "[\"a\", \"ñ\", \"b\"]"
+        );
+        assert_eq!(evaluate_string("join([\"a\", 1, nil], \"-\")"), "a-1-nil");
+        assert!(matches!(
+            evaluate("join(\"ab\", \"\")"),
+            Err(RuntimeError::TypeError)
+        ));
+    }
+
+    #[test]
+    fn test_case_and_whitespace() {
+        assert_eq!(evaluate_string("upper(\"straße\")"), "STRASSE");
+        assert_eq!(evaluate_string("lower(\"ÀB\")"), "àb");
+        assert_eq!(evaluate_string("trim(\"  a b \t\")"), "a b");
+    }
+
+    #[test]
+    fn test_replace_and_starts_with() {
+        assert_eq!(evaluate_string("replace(\"a-b-c\", \"-\", \"+\")"), "a+b+c");
+        assert!(matches!(
+            evaluate("replace(\"abc\", \"\", \"x\")"),
+            Err(RuntimeError::InvalidArgument)
+        ));
+        assert!(matches!(
+            evaluate("startsWith(\"lox\", \"lo\")"),
+            Ok(Value::Boolean(true))
+        ));
+    }
+
+    #[test]
+    fn test_chr_and_ord() {
+        assert_eq!(evaluate_string("chr(233)"), "é");
+        assert!(matches!(
+            evaluate("ord(\"😀\")"),
+            Ok(Value::Numeric(128512.0))
+        ));
+        assert!(matches!(
+            evaluate("chr(55296)"),
+            Err(RuntimeError::InvalidArgument)
+        ));
+        assert!(matches!(
+            evaluate("ord(\"ab\")"),
+            Err(RuntimeError::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(evaluate_string("str(1.5)"), "1.5");
+        assert_eq!(evaluate_string("str([1, \"a\"])"), "[1, \"a\"]");
+        assert!(matches!(
+            evaluate("num(\"12.5\")"),
+            Ok(Value::Numeric(12.5))
+        ));
+        assert!(matches!(evaluate("num(\"-3\")"), Ok(Value::Numeric(-3.0))));
+
+        for input in ["", " 1", "1.", ".5", "1e5", "+1", "inf", "NaN", "--1"] {
+            assert!(
+                matches!(
+                    evaluate(&format!("num(\"{input}\")")),
+                    Err(RuntimeError::InvalidArgument)
+                ),
+                "{input:?} should not parse"
+            );
+        }
+
+        assert!(matches!(evaluate("num(1)"), Err(RuntimeError::TypeError)));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::evaluator::{Arity, EvaluatorResult};
+use crate::native::{Host, NativeValue, Natives};
+
+pub fn register<V: NativeValue>(natives: &mut impl Natives<V>) {
+    natives.native("time", Arity::Exactly(0), time);
+    natives.native("clock", Arity::Exactly(0), clock);
+}
+
+/* whole seconds since the unix epoch */
+fn time<V: NativeValue>(host: &mut dyn Host, _: &[V]) -> EvaluatorResult<V> {
+    Ok(V::from(host.system().now().as_secs() as f64))
+}
+
+/* seconds since the unix epoch, to the millisecond */
+fn clock<V: NativeValue>(host: &mut dyn Host, _: &[V]) -> EvaluatorResult<V> {
+    Ok(V::from(host.system().now().as_millis() as f64 / 1000.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::ManualClock;
+    use crate::evaluator::{Evaluator, Value};
+    use crate::output::OutputBuffer;
+    use crate::test_util::run;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_manual_clock() {
+        let mut evaluator = Evaluator::new();
+        let clock = ManualClock::new(Duration::from_millis(12_345));
+        let output = OutputBuffer::new();
+
+        evaluator.set_time_source(clock.clone());
+        evaluator.set_output(output.clone());
+
+        run(&mut evaluator, "print time(); print clock();").unwrap();
+        clock.advance(Duration::from_millis(1_500));
+        run(&mut evaluator, "print clock();").unwrap();
+
+        assert_eq!(output.contents(), "12\n12.345\n13.845\n");
+    }
+
+    #[test]
+    fn test_closure_time_source() {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_time_source(|| Duration::from_secs(60));
+
+        run(&mut evaluator, "var elapsed = clock() - time();").unwrap();
+
+        assert!(matches!(
+            evaluator.global("elapsed"),
+            Some(Value::Numeric(0.0))
+        ));
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let mut evaluator = Evaluator::new();
+
+        run(&mut evaluator, "var start = clock();").unwrap();
+
+        assert!(matches!(evaluator.global("start"), Some(Value::Numeric(start)) if start > 0.0));
+    }
+}
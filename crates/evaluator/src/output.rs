@@ -0,0 +1,94 @@
+use std::cell::RefCell;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+/* where `print` statements write to, stdout unless the host says otherwise. `eprint` writes to
+ * a second one, which is stderr by default */
+pub struct Output {
+    sink: Box<dyn Write>,
+}
+
+impl Output {
+    pub fn new(sink: Box<dyn Write>) -> Self {
+        Output { sink }
+    }
+
+    pub fn stderr() -> Self {
+        Output::new(Box::new(std::io::stderr()))
+    }
+
+    pub fn sink(&mut self) -> &mut dyn Write {
+        &mut self.sink
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output::new(Box::new(std::io::stdout()))
+    }
+}
+
+impl std::fmt::Debug for Output {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Output").finish_non_exhaustive()
+    }
+}
+
+/* where `readLine` reads from. stdin is locked only while a line is read, so that the host can
+ * still read from it between evaluations */
+#[derive(Default)]
+pub struct Input {
+    source: Option<Box<dyn BufRead>>,
+}
+
+impl Input {
+    pub fn new(source: Box<dyn BufRead>) -> Self {
+        Input {
+            source: Some(source),
+        }
+    }
+
+    pub fn read_line(&mut self, line: &mut String) -> std::io::Result<usize> {
+        match &mut self.source {
+            Some(source) => source.read_line(line),
+            None => std::io::stdin().lock().read_line(line),
+        }
+    }
+}
+
+impl std::fmt::Debug for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Input").finish_non_exhaustive()
+    }
+}
+
+/// An in-memory output sink. Clones share the same buffer, so a host can keep one to read back
+/// what a script printed into the other.
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer {
+    contents: Rc<RefCell<Vec<u8>>>,
+}
+
+impl OutputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.contents.borrow()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.contents.borrow_mut().clear();
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.contents.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::clock::{Clock, TimeSource};
+use crate::output::{Input, Output};
+
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+/// The outside world as scripts see it: where `print` and `eprint` write to, where `readLine`
+/// reads from, the clock, and whether natives may touch the filesystem and the console at all.
+/// Both engines keep one, so that a host sets them up the same way whichever runs the script.
+#[derive(Debug)]
+pub struct System {
+    output: Output,
+    error_output: Output,
+    input: Input,
+    clock: Clock,
+    filesystem_access: bool,
+    console_access: bool,
+}
+
+impl System {
+    pub fn new() -> Self {
+        System {
+            output: Output::default(),
+            error_output: Output::stderr(),
+            input: Input::default(),
+            clock: Clock::default(),
+            filesystem_access: true,
+            console_access: true,
+        }
+    }
+
+    pub fn output(&mut self) -> &mut dyn Write {
+        self.output.sink()
+    }
+
+    /// Redirects the output of `print` statements, which goes to stdout by default.
+    pub fn set_output(&mut self, sink: impl Write + 'static) {
+        self.output = Output::new(Box::new(sink));
+    }
+
+    pub fn error_output(&mut self) -> &mut dyn Write {
+        self.error_output.sink()
+    }
+
+    /// Redirects the output of the `eprint` native, which goes to stderr by default.
+    pub fn set_error_output(&mut self, sink: impl Write + 'static) {
+        self.error_output = Output::new(Box::new(sink));
+    }
+
+    /// Reads a line of input, with its line ending, into `line`. Reads nothing at the end of
+    /// input, like `BufRead::read_line`.
+    pub fn read_line(&mut self, line: &mut String) -> std::io::Result<usize> {
+        self.input.read_line(line)
+    }
+
+    /// Replaces where the `readLine` native reads from, which is stdin by default.
+    pub fn set_input(&mut self, source: impl BufRead + 'static) {
+        self.input = Input::new(Box::new(source));
+    }
+
+    /// The current time according to the time source, as a duration since the unix epoch.
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Replaces the time source of the `time` and `clock` natives, which is the system clock by
+    /// default.
+    pub fn set_time_source(&mut self, source: impl TimeSource + 'static) {
+        self.clock = Clock::new(Box::new(source));
+    }
+
+    pub fn filesystem_access(&self) -> bool {
+        self.filesystem_access
+    }
+
+    /// Allows or denies the filesystem natives, such as `readFile`, which fail with
+    /// `RuntimeError::FilesystemAccessDenied` once denied.
+    pub fn set_filesystem_access(&mut self, allowed: bool) {
+        self.filesystem_access = allowed;
+    }
+
+    pub fn console_access(&self) -> bool {
+        self.console_access
+    }
+
+    /// Allows or denies the console natives, `readLine` and `eprint`, which fail with
+    /// `RuntimeError::ConsoleAccessDenied` once denied.
+    pub fn set_console_access(&mut self, allowed: bool) {
+        self.console_access = allowed;
+    }
+}
+
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,48 @@
+//! Helpers shared by the unit tests throughout the crate.
+
+use crate::evaluator::{Evaluator, RuntimeError, Value};
+
+/// Every value other than nil and false, which are the only falsey ones.
+pub const TRUTHY: [&str; 6] = ["true", "0", "\"\"", "[]", "{}", "fun () {}"];
+
+pub fn parse(code: &str) -> parser::Ast {
+    let tokens = lexer::tokenize(code).unwrap();
+    let mut ast = parser::Ast::new(tokens.into_iter()).unwrap();
+    ast.resolve().unwrap();
+
+    ast
+}
+
+pub fn run(evaluator: &mut Evaluator, code: &str) -> Result<(), RuntimeError> {
+    evaluator.evaluate(&parse(code))
+}
+
+/// Runs a program and returns the value it leaves in the global `result`.
+pub fn run_with(evaluator: &mut Evaluator, code: &str) -> Result<Value, RuntimeError> {
+    run(evaluator, code)?;
+
+    Ok(evaluator.global("result").unwrap())
+}
+
+pub fn result_of(code: &str) -> Result<Value, RuntimeError> {
+    run_with(&mut Evaluator::new(), code)
+}
+
+/// Evaluates an expression on a fresh evaluator, with an unset global `x` at hand.
+pub fn evaluate(expression: &str) -> Result<Value, RuntimeError> {
+    result_of(&format!("var x; var result = {expression};"))
+}
+
+pub fn evaluate_number(expression: &str) -> f64 {
+    match evaluate(expression) {
+        Ok(Value::Numeric(value)) => value,
+        other => panic!("expected a number from {expression}, got {other:?}"),
+    }
+}
+
+pub fn evaluate_string(expression: &str) -> String {
+    match evaluate(expression) {
+        Ok(Value::String_(value)) => String::from(&*value),
+        other => panic!("expected a string from {expression}, got {other:?}"),
+    }
+}
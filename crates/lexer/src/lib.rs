@@ -1,5 +1,8 @@
+pub mod symbol;
 pub mod tokens;
 
+pub use symbol::Symbol;
+
 use tokens::*;
 
 #[derive(Debug, Clone)]
@@ -34,10 +37,7 @@ impl Token {
             Token::from(Identifier::extract(input))
         } else {
             /* synchronize on space */
-            let new_input = input.trim_start_matches(|c| !is_skippable_whitespace(c));
-            let token = &input[..new_input.len()];
-            *input = new_input;
-            //Ok(Token::Error(LexError::NoTokenKind, String::from(input[..new_input.len()])))
+            *input = input.trim_start_matches(|c| !is_skippable_whitespace(c));
             Token::Error(LexError::NoTokenKind)
         }
     }
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::{LazyLock, Mutex};
+
+/// An interned string. Interning the same text twice gives the same symbol, so symbols compare
+/// and hash by address instead of by their contents.
+///
+/// The text of every symbol lives for the rest of the program, so only identifiers are interned:
+/// the names in source code and the names hosts define globals under. String literals and the
+/// strings scripts build are not, but a long-running process that reads ever new names, such as
+/// a REPL, still grows the table with each one.
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
+
+static INTERNER: LazyLock<Mutex<HashSet<&'static str>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+impl Symbol {
+    pub fn intern(text: &str) -> Self {
+        let mut interner = INTERNER.lock().unwrap();
+
+        match interner.get(text) {
+            Some(interned) => Symbol(interned),
+            None => {
+                let interned: &'static str = Box::leak(Box::from(text));
+                interner.insert(interned);
+                Symbol(interned)
+            }
+        }
+    }
+
+    /// The symbol for `text` if it has been interned, without interning it otherwise.
+    pub fn get(text: &str) -> Option<Self> {
+        INTERNER.lock().unwrap().get(text).copied().map(Symbol)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_shares_text() {
+        let first = Symbol::intern("counter");
+        let second = Symbol::intern(&String::from("counter"));
+
+        assert_eq!(first, second);
+        assert!(std::ptr::eq(first.as_str(), second.as_str()));
+        assert_ne!(first, Symbol::intern("Counter"));
+    }
+
+    #[test]
+    fn test_get_does_not_intern() {
+        assert_eq!(Symbol::get("neverInternedBefore"), None);
+
+        let symbol = Symbol::intern("internedNow");
+        assert_eq!(Symbol::get("internedNow"), Some(symbol));
+    }
+}
@@ -15,18 +15,22 @@ pub trait LookaheadLex: std::fmt::Debug {
     ///
     /// # Examples
     /// ```
-    /// # use lib::lexer::*;
+    /// # use lexer::Symbol;
+    /// # use lexer::tokens::*;
     /// let mut code = "var x = 5;";
     ///
     /// let token = FixedToken::extract(&mut code).unwrap();
     /// assert!(matches!(token, FixedToken::Var));
     ///
+    /// code = code.trim_start();
     /// let token = Identifier::extract(&mut code).unwrap();
-    /// assert_eq!(token, Identifier{name: String::from("x")});
+    /// assert_eq!(token, Identifier{name: Symbol::intern("x")});
     ///
+    /// code = code.trim_start();
     /// let token = FixedToken::extract(&mut code).unwrap();
     /// assert!(matches!(token, FixedToken::Equal));
     ///
+    /// code = code.trim_start();
     /// let token = NumericLiteral::extract(&mut code).unwrap();
     /// assert_eq!(token, NumericLiteral{value: 5f64});
     ///
@@ -5,14 +5,27 @@ pub enum FixedToken {
     RightParenthesis,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Plus,
+    PlusPlus,
+    PlusEqual,
     Minus,
+    MinusMinus,
+    MinusEqual,
     Asterisk,
+    AsteriskAsterisk,
+    AsteriskEqual,
     ForwardSlash,
+    ForwardSlashForwardSlash,
+    ForwardSlashEqual,
+    Percent,
+    PercentEqual,
     Bang,
     BangEqual,
     Equal,
     EqualEqual,
+    EqualGreater,
     Greater,
     GreaterEqual,
     Less,
@@ -20,6 +33,8 @@ pub enum FixedToken {
     Comma,
     Dot,
     Semicolon,
+    Question,
+    Colon,
 
     /* Literals */
     True,
@@ -56,11 +71,12 @@ pub enum FixedToken {
  * Additionally, test whether checking shorter but less frequent tokens
  * before more frequent but longer tokens has a measurable difference.
  *
- * Note: longer fixed tokens which contain tokens within them (>=, <=, ==, !=)
+ * Note: longer fixed tokens which contain tokens within them (>=, <=, ==, !=, //, **, +=, ...)
  * must come before their shorter subtokens in order to be parsed correctly.
  *
  * Note: Minus is not in this map as it is separately checked to avoid the cost of determining
- * whether it is a part of a numeric literal.
+ * whether it is a part of a numeric literal. The longer -- and -= tokens are, since neither can
+ * start a numeric literal.
  */
 const FIXED_TOKEN_MAP: &[(&str, FixedToken)] = &[
     (">=", FixedToken::GreaterEqual),
@@ -68,6 +84,7 @@ const FIXED_TOKEN_MAP: &[(&str, FixedToken)] = &[
     ("<=", FixedToken::LessEqual),
     ("<", FixedToken::Less),
     ("==", FixedToken::EqualEqual),
+    ("=>", FixedToken::EqualGreater),
     ("=", FixedToken::Equal),
     ("!=", FixedToken::BangEqual),
     ("!", FixedToken::Bang),
@@ -75,12 +92,26 @@ const FIXED_TOKEN_MAP: &[(&str, FixedToken)] = &[
     (")", FixedToken::RightParenthesis),
     ("{", FixedToken::LeftBrace),
     ("}", FixedToken::RightBrace),
+    ("[", FixedToken::LeftBracket),
+    ("]", FixedToken::RightBracket),
     (",", FixedToken::Comma),
     (".", FixedToken::Dot),
+    ("++", FixedToken::PlusPlus),
+    ("+=", FixedToken::PlusEqual),
     ("+", FixedToken::Plus),
+    ("--", FixedToken::MinusMinus),
+    ("-=", FixedToken::MinusEqual),
     (";", FixedToken::Semicolon),
+    ("?", FixedToken::Question),
+    (":", FixedToken::Colon),
+    ("//", FixedToken::ForwardSlashForwardSlash),
+    ("/=", FixedToken::ForwardSlashEqual),
     ("/", FixedToken::ForwardSlash),
+    ("**", FixedToken::AsteriskAsterisk),
+    ("*=", FixedToken::AsteriskEqual),
     ("*", FixedToken::Asterisk),
+    ("%=", FixedToken::PercentEqual),
+    ("%", FixedToken::Percent),
     ("true", FixedToken::True),
     ("false", FixedToken::False),
     ("nil", FixedToken::Nil),
@@ -104,6 +135,15 @@ impl FixedToken {
     pub fn extract(input: &mut &str) -> Option<Self> {
         for (token_string, token) in FIXED_TOKEN_MAP {
             if let Some(rest_of_input) = input.strip_prefix(token_string) {
+                /* a keyword is only a keyword if it isn't the start of a longer
+                 * identifier, as `or` is in `ord`
+                 */
+                if token_string.starts_with(char::is_alphabetic)
+                    && rest_of_input.starts_with(char::is_alphanumeric)
+                {
+                    continue;
+                }
+
                 *input = rest_of_input;
                 return Some(token.clone());
             }
@@ -140,8 +180,22 @@ mod tests {
         for &(mut token_string, ref expected_token) in FIXED_TOKEN_MAP {
             assert!(matches!(
                 FixedToken::extract(&mut token_string),
-                Some(actual_token) if std::mem::discriminant(&actual_token) == std::mem::discriminant(&expected_token)
+                Some(actual_token) if std::mem::discriminant(&actual_token) == std::mem::discriminant(expected_token)
             ));
         }
     }
+
+    #[test]
+    fn test_keyword_prefix() {
+        for mut input in ["ord", "format", "variable", "nil2"] {
+            assert!(FixedToken::extract(&mut input).is_none());
+        }
+
+        let mut input = "or d";
+        assert!(matches!(
+            FixedToken::extract(&mut input),
+            Some(FixedToken::Or)
+        ));
+        assert_eq!(input, " d");
+    }
 }
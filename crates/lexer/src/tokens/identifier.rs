@@ -1,9 +1,9 @@
 use super::LookaheadLex;
-use crate::{LexResult, Token};
+use crate::{LexResult, Symbol, Token};
 
 #[derive(Debug, PartialEq)]
 pub struct Identifier {
-    pub name: String,
+    pub name: Symbol,
 }
 
 impl LookaheadLex for Identifier {
@@ -16,21 +16,21 @@ impl LookaheadLex for Identifier {
     }
 
     fn extract(input: &mut &str) -> LexResult<Self> {
-        for (i, c) in input.chars().enumerate() {
+        for (i, c) in input.char_indices() {
             if !c.is_alphanumeric() {
                 let token = &input[..i];
                 *input = &input[i..];
                 return Ok(Identifier {
-                    name: String::from(token),
+                    name: Symbol::intern(token),
                 });
             }
         }
 
-        let token = input.clone();
+        let token = *input;
         *input = "";
-        return Ok(Identifier {
-            name: String::from(token),
-        });
+        Ok(Identifier {
+            name: Symbol::intern(token),
+        })
     }
 }
 
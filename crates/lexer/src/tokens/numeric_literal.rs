@@ -94,7 +94,7 @@ mod tests {
         for record in records {
             assert!(
                 record.0
-                    == matches!(NumericLiteral::extract(&mut record.1.as_str()), Some(actual) if actual.value == record.2.value)
+                    == matches!(NumericLiteral::extract(&mut record.1.as_str()), Ok(actual) if actual.value == record.2.value)
             );
         }
     }
@@ -1,9 +1,12 @@
 use super::LookaheadLex;
 use crate::{LexError, LexResult, Token};
 
+use std::rc::Rc;
+
+/* unlike identifiers, literals aren't interned, as interned text is never freed */
 #[derive(Debug)]
 pub struct StringLiteral {
-    pub value: String,
+    pub value: Rc<str>,
 }
 
 impl LookaheadLex for StringLiteral {
@@ -12,13 +15,13 @@ impl LookaheadLex for StringLiteral {
     }
 
     fn extract(input: &mut &str) -> LexResult<Self> {
-        for (i, c) in input.chars().enumerate().skip(1) {
+        for (i, c) in input.char_indices().skip(1) {
             match c {
                 '"' => {
                     let token = &input[1..i];
                     *input = &input[i + 1..];
                     return Ok(StringLiteral {
-                        value: String::from(token),
+                        value: Rc::from(token),
                     });
                 }
                 '\n' => {
@@ -29,6 +32,7 @@ impl LookaheadLex for StringLiteral {
             }
         }
 
+        *input = "";
         Err(LexError::UnclosedString)
     }
 }
@@ -38,3 +42,35 @@ impl From<StringLiteral> for Token {
         Token::StringLiteral(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_unicode() {
+        let mut input = "\"héllo\" + x";
+        let literal = StringLiteral::extract(&mut input).unwrap();
+
+        assert_eq!(&*literal.value, "héllo");
+        assert_eq!(input, " + x");
+    }
+
+    #[test]
+    fn test_extract_does_not_intern() {
+        let mut input = "\"neverAnIdentifier\"";
+        StringLiteral::extract(&mut input).unwrap();
+
+        assert_eq!(crate::Symbol::get("neverAnIdentifier"), None);
+    }
+
+    #[test]
+    fn test_extract_unclosed() {
+        let mut input = "\"abc";
+        assert!(matches!(
+            StringLiteral::extract(&mut input),
+            Err(LexError::UnclosedString)
+        ));
+        assert!(input.is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+//! Embeds the Lox interpreter in a host application.
+//!
+//! ```
+//! use lox::{Arity, Interpreter, Value};
+//!
+//! let mut interpreter = Interpreter::new();
+//!
+//! interpreter
+//!     .define_native("double", Arity::Exactly(1), |_, arguments| {
+//!         let value = f64::try_from(arguments[0].clone())?;
+//!         Ok(Value::from(value * 2.0))
+//!     })
+//!     .unwrap();
+//!
+//! interpreter
+//!     .run_source("fun add(a, b) { return double(a) + b; }")
+//!     .unwrap();
+//!
+//! let sum = interpreter
+//!     .call_function("add", vec![Value::from(1.0), Value::from(2.0)])
+//!     .unwrap();
+//!
+//! assert_eq!(f64::try_from(sum).unwrap(), 4.0);
+//! ```
+
+pub use evaluator::{
+    Arity, Context, GcStats, InterruptHandle, Limits, ManualClock, NativeFunction, OutputBuffer,
+    RuntimeError, SystemClock, TimeSource, Value,
+};
+pub use lexer::LexError;
+pub use parser::{ParseErrorKind, ResolveError};
+
+#[derive(Debug)]
+pub enum Error {
+    Lex(Vec<LexError>),
+    Parse(error::Errors<ParseErrorKind>),
+    Resolve(Vec<ResolveError>),
+    Runtime(RuntimeError),
+}
+
+impl From<RuntimeError> for Error {
+    fn from(error: RuntimeError) -> Self {
+        Error::Runtime(error)
+    }
+}
+
+/// A Lox interpreter whose globals persist across the sources it runs.
+pub struct Interpreter {
+    evaluator: evaluator::Evaluator,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            evaluator: evaluator::Evaluator::new(),
+        }
+    }
+
+    /// Lexes, parses, resolves and runs a Lox program.
+    pub fn run_source(&mut self, source: &str) -> Result<(), Error> {
+        let tokens = lexer::tokenize(source).map_err(|tokens| {
+            Error::Lex(
+                tokens
+                    .into_iter()
+                    .filter_map(|token| match token {
+                        lexer::Token::Error(error) => Some(error),
+                        _ => None,
+                    })
+                    .collect(),
+            )
+        })?;
+
+        let mut ast = parser::Ast::new(tokens.into_iter()).map_err(Error::Parse)?;
+        ast.resolve().map_err(Error::Resolve)?;
+
+        Ok(self.evaluator.evaluate(&ast)?)
+    }
+
+    /// Calls the global function `name`, which may be written in Lox or be a native.
+    pub fn call_function(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value, Error> {
+        let callee = self
+            .evaluator
+            .global(name)
+            .ok_or(RuntimeError::VariableDoesNotExist)?;
+
+        Ok(self.evaluator.call(&callee, arguments)?)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.evaluator.global(name)
+    }
+
+    /// Sets a global variable, declaring it if the scripts haven't.
+    pub fn set_global(&mut self, name: &str, value: impl Into<Value>) {
+        self.evaluator.set_global(name, value.into())
+    }
+
+    /// Redirects the output of `print` statements, which goes to stdout by default.
+    pub fn set_output(&mut self, sink: impl std::io::Write + 'static) {
+        self.evaluator.set_output(sink)
+    }
+
+    /// A handle that stops the source or function the interpreter is running, from another
+    /// thread or a signal handler.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.evaluator.interrupt_handle()
+    }
+
+    /// Bounds the work each later call to `run_source` or `call_function` may do.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.evaluator.set_limits(limits)
+    }
+
+    /// Replaces the clock read by the `time` and `clock` natives, which is the system clock by
+    /// default.
+    pub fn set_time_source(&mut self, source: impl TimeSource + 'static) {
+        self.evaluator.set_time_source(source)
+    }
+
+    /// Allows or denies scripts access to the filesystem, which they have by default.
+    pub fn set_filesystem_access(&mut self, allowed: bool) {
+        self.evaluator.set_filesystem_access(allowed)
+    }
+
+    /// Redirects what scripts write with `eprint`, which goes to stderr by default.
+    pub fn set_error_output(&mut self, sink: impl std::io::Write + 'static) {
+        self.evaluator.set_error_output(sink)
+    }
+
+    /// Replaces what scripts read with `readLine`, which is stdin by default.
+    pub fn set_input(&mut self, source: impl std::io::BufRead + 'static) {
+        self.evaluator.set_input(source)
+    }
+
+    /// Allows or denies scripts access to the input and error output, which they have by
+    /// default.
+    pub fn set_console_access(&mut self, allowed: bool) {
+        self.evaluator.set_console_access(allowed)
+    }
+
+    /// Frees values that scripts can no longer reach but that refer to each other in a cycle.
+    /// The interpreter also does this on its own as scripts allocate.
+    pub fn collect_garbage(&mut self) {
+        self.evaluator.collect_garbage()
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        self.evaluator.gc_stats()
+    }
+
+    /// Registers a function implemented in Rust under a global name.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut Context, &[Value]) -> Result<Value, RuntimeError> + 'static,
+    ) -> Result<(), Error> {
+        Ok(self.evaluator.define_native(name, arity, function)?)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_globals_persist() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.run_source("var a = 1;").unwrap();
+        interpreter.run_source("a = a + 1;").unwrap();
+
+        let a = interpreter.get_global("a").unwrap();
+        assert_eq!(f64::try_from(a).unwrap(), 2.0);
+    }
+
+    #[test]
+    fn test_set_global() {
+        let mut interpreter = Interpreter::new();
+
+        interpreter.set_global("name", "lox");
+        interpreter.set_global("numbers", vec![1.0, 2.0]);
+        interpreter
+            .run_source("var greeting = \"hello \" + name; push(numbers, 3);")
+            .unwrap();
+
+        let greeting = interpreter.get_global("greeting").unwrap();
+        assert_eq!(String::try_from(greeting).unwrap(), "hello lox");
+
+        let numbers = interpreter.get_global("numbers").unwrap();
+        assert_eq!(Vec::<f64>::try_from(numbers).unwrap(), [1.0, 2.0, 3.0]);
+
+        interpreter.set_global("name", true);
+        assert!(bool::try_from(interpreter.get_global("name").unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_call_function() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .run_source("fun greet(name) { return \"hi \" + name; } var f = x => x;")
+            .unwrap();
+
+        let greeting = interpreter.call_function("greet", vec![Value::from("bob")]);
+        assert_eq!(String::try_from(greeting.unwrap()).unwrap(), "hi bob");
+
+        assert!(matches!(
+            interpreter.call_function("f", vec![Value::from(())]),
+            Ok(Value::Nil)
+        ));
+        assert!(matches!(
+            interpreter.call_function("missing", Vec::new()),
+            Err(Error::Runtime(RuntimeError::VariableDoesNotExist))
+        ));
+        assert!(matches!(
+            interpreter.call_function("greet", Vec::new()),
+            Err(Error::Runtime(RuntimeError::WrongNumberOfArguments))
+        ));
+    }
+
+    #[test]
+    fn test_conversion_type_error() {
+        assert!(matches!(
+            f64::try_from(Value::from("1")),
+            Err(RuntimeError::TypeError)
+        ));
+        assert!(matches!(
+            Vec::<bool>::try_from(Value::from(vec![true.into(), Value::Nil])),
+            Err(RuntimeError::TypeError)
+        ));
+        assert!(matches!(Value::from(None::<f64>), Value::Nil));
+    }
+
+    #[test]
+    fn test_output() {
+        let mut interpreter = Interpreter::new();
+        let output = OutputBuffer::new();
+
+        interpreter.set_output(output.clone());
+        interpreter.run_source("print 1; print [\"a\"];").unwrap();
+
+        assert_eq!(output.contents(), "1\n[\"a\"]\n");
+    }
+
+    #[test]
+    fn test_filesystem_access() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_filesystem_access(false);
+
+        assert!(matches!(
+            interpreter.run_source("readFile(\"Cargo.toml\");"),
+            Err(Error::Runtime(RuntimeError::FilesystemAccessDenied))
+        ));
+    }
+
+    #[test]
+    fn test_console_access() {
+        let mut interpreter = Interpreter::new();
+        let errors = OutputBuffer::new();
+
+        interpreter.set_error_output(errors.clone());
+        interpreter.set_input(std::io::Cursor::new("a\n"));
+        interpreter.run_source("eprint(readLine());").unwrap();
+        assert_eq!(errors.contents(), "a\n");
+
+        interpreter.set_console_access(false);
+        assert!(matches!(
+            interpreter.run_source("eprint(1);"),
+            Err(Error::Runtime(RuntimeError::ConsoleAccessDenied))
+        ));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut interpreter = Interpreter::new();
+
+        assert!(matches!(
+            interpreter.run_source("var s = \"unclosed;"),
+            Err(Error::Lex(errors)) if matches!(errors[..], [LexError::UnclosedString])
+        ));
+        assert!(matches!(
+            interpreter.run_source("var = 1;"),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            interpreter.run_source("return 1;"),
+            Err(Error::Resolve(_))
+        ));
+        assert!(matches!(
+            interpreter.run_source("1 % 0;"),
+            Err(Error::Runtime(RuntimeError::DivisionByZero))
+        ));
+    }
+}
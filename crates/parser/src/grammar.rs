@@ -2,23 +2,44 @@ mod declaration;
 mod expression;
 mod statement;
 
+use lexer::Symbol;
+
+use std::rc::Rc;
+
 #[derive(Debug)]
 pub struct Program {
     pub declarations: Vec<Declaration>,
 }
 
+/// A declaration together with the line, counting from 1, that it starts on.
+#[derive(Debug)]
+pub struct Declaration {
+    pub kind: DeclarationKind,
+    pub line: usize,
+}
+
 #[derive(Debug)]
-pub enum Declaration {
+pub enum DeclarationKind {
     VariableDeclaration(VariableDeclaration),
+    FunctionDeclaration(Rc<Function>),
     Statement(Statement),
 }
 
 #[derive(Debug)]
 pub struct VariableDeclaration {
-    pub identifier: String,
+    pub identifier: Symbol,
     pub value: Option<Expression>,
 }
 
+/// A function definition, shared by `fun` declarations and function expressions. It is reference
+/// counted so that function values can outlive the tree they were parsed from.
+#[derive(Debug)]
+pub struct Function {
+    pub name: Option<Symbol>,
+    pub parameters: Vec<Symbol>,
+    pub body: Vec<Declaration>,
+}
+
 #[derive(Debug)]
 pub enum Statement {
     ExpressionStatement(Expression),
@@ -34,6 +55,7 @@ pub enum Statement {
         else_: Option<Box<Statement>>,
     },
     PrintStatement(Expression),
+    ReturnStatement(Option<Expression>),
     WhileStatement {
         condition: Expression,
         body: Box<Statement>,
@@ -54,15 +76,98 @@ pub struct Block {
 
 #[derive(Debug)]
 pub enum Expression {
-    Assignment{
-        identifier: String,
-        value: Box<Expression>,
-    },
+    Assignment(Assignment),
+    Increment(Increment),
+    Conditional(Conditional),
+    Call(Call),
+    Property(Property),
+    Index(Index),
     Unary(Unary),
     Binary(Binary),
     Primary(Primary),
 }
 
+/// A use of a variable by name. The resolver fills in where a local variable lives; a variable
+/// left unresolved is a global.
+#[derive(Debug)]
+pub struct Variable {
+    pub name: Symbol,
+    pub local: Option<Local>,
+}
+
+impl Variable {
+    pub fn new(name: Symbol) -> Self {
+        Variable { name, local: None }
+    }
+}
+
+/// The location of a local variable: `depth` scopes out from its use, at the `slot`-th position in
+/// the order that scope declares its variables.
+#[derive(Debug, Clone, Copy)]
+pub struct Local {
+    pub depth: usize,
+    pub slot: usize,
+}
+
+#[derive(Debug)]
+pub struct Assignment {
+    pub target: AssignmentTarget,
+    /* None for plain `=`, otherwise the operator of a compound assignment such as `+=` */
+    pub operator: Option<BinaryOperator>,
+    pub value: Box<Expression>,
+}
+
+#[derive(Debug)]
+pub struct Increment {
+    pub target: AssignmentTarget,
+    pub operator: IncrementOperator,
+    pub fixity: Fixity,
+}
+
+#[derive(Debug)]
+pub enum AssignmentTarget {
+    Identifier(Variable),
+    Property(Property),
+    Index(Index),
+}
+
+#[derive(Debug)]
+pub enum IncrementOperator {
+    Increment,
+    Decrement,
+}
+
+#[derive(Debug)]
+pub enum Fixity {
+    Prefix,
+    Postfix,
+}
+
+#[derive(Debug)]
+pub struct Conditional {
+    pub condition: Box<Expression>,
+    pub then: Box<Expression>,
+    pub else_: Box<Expression>,
+}
+
+#[derive(Debug)]
+pub struct Call {
+    pub callee: Box<Expression>,
+    pub arguments: Vec<Expression>,
+}
+
+#[derive(Debug)]
+pub struct Property {
+    pub object: Box<Expression>,
+    pub name: Symbol,
+}
+
+#[derive(Debug)]
+pub struct Index {
+    pub object: Box<Expression>,
+    pub index: Box<Expression>,
+}
+
 #[derive(Debug)]
 pub struct Unary {
     pub operator: UnaryOperator,
@@ -90,6 +195,9 @@ pub enum BinaryOperator {
     Subtraction,
     Multiplication,
     Division,
+    IntegerDivision,
+    Modulo,
+    Exponentiation,
 }
 
 #[derive(Debug)]
@@ -100,15 +208,15 @@ pub enum UnaryOperator {
 
 #[derive(Debug)]
 pub enum Primary {
-    Call {
-        callable: Box<Primary>,
-        arguments: Vec<Expression>,
-    },
     True,
     False,
     Nil,
     Number(f64),
-    String_(String),
-    Identifier(String),
+    /* equal string constants in one program share their storage */
+    String_(Rc<str>),
+    Identifier(Variable),
     Grouping(Box<Expression>),
+    List(Vec<Expression>),
+    Map(Vec<(Expression, Expression)>),
+    Function(Rc<Function>),
 }
@@ -1,7 +1,9 @@
 use crate::grammar::*;
 use crate::parser::*;
 
-use lexer::{Token, tokens::FixedToken};
+use lexer::{Symbol, Token, tokens::FixedToken};
+
+use std::rc::Rc;
 
 impl Program {
     pub fn parse<T: Iterator<Item = Token>>(
@@ -55,12 +57,108 @@ impl Declaration {
     pub fn parse<T: Iterator<Item = Token>>(
         parse_context: &mut ParseContext<T>,
     ) -> ParseResult<Self> {
-        match parse_context.tokens().peek().expect("Expected tokens") {
-            Token::FixedToken(FixedToken::Var) => Ok(Declaration::VariableDeclaration(
-                VariableDeclaration::parse(parse_context)?,
-            )),
-            _ => Ok(Declaration::Statement(Statement::parse(parse_context)?)),
+        let line = parse_context.line();
+
+        let kind = match parse_context.tokens().peek().expect("Expected tokens") {
+            Token::FixedToken(FixedToken::Var) => {
+                DeclarationKind::VariableDeclaration(VariableDeclaration::parse(parse_context)?)
+            }
+            Token::FixedToken(FixedToken::Fun) => {
+                parse_context.tokens().next();
+
+                let Some(Token::Identifier(identifier)) = parse_context.tokens().next() else {
+                    parse_context.record_error(ParseErrorKind::ExpectedIdentifier);
+                    return Err(ShouldSynchronize::Yes);
+                };
+
+                DeclarationKind::FunctionDeclaration(Rc::new(Function::parse(
+                    parse_context,
+                    Some(identifier.name),
+                )?))
+            }
+            _ => DeclarationKind::Statement(Statement::parse(parse_context)?),
+        };
+
+        Ok(Declaration { kind, line })
+    }
+}
+
+impl Function {
+    /// Parses a parameter list and block body, starting at the opening parenthesis.
+    pub fn parse<T: Iterator<Item = Token>>(
+        parse_context: &mut ParseContext<T>,
+        name: Option<Symbol>,
+    ) -> ParseResult<Self> {
+        parse_context.match_token(FixedToken::LeftParenthesis)?;
+
+        let mut parameters: Vec<Symbol> = Vec::new();
+
+        if let Some(Token::FixedToken(FixedToken::RightParenthesis)) = parse_context.tokens().peek()
+        {
+            parse_context.tokens().next();
+        } else {
+            loop {
+                let Some(Token::Identifier(identifier)) = parse_context.tokens().next() else {
+                    parse_context.record_error(ParseErrorKind::ExpectedIdentifier);
+                    return Err(ShouldSynchronize::Yes);
+                };
+
+                parameters.push(identifier.name);
+
+                match parse_context.tokens().next() {
+                    Some(Token::FixedToken(FixedToken::Comma)) => (),
+                    Some(Token::FixedToken(FixedToken::RightParenthesis)) => break,
+                    _ => {
+                        parse_context.record_error(ParseErrorKind::UnexpectedToken);
+                        return Err(ShouldSynchronize::Yes);
+                    }
+                }
+            }
+        }
+
+        if parameters.len() > MAX_ARGUMENTS {
+            parse_context.record_error(ParseErrorKind::TooManyParameters);
+            return Err(ShouldSynchronize::Yes);
+        }
+
+        let Some(Token::FixedToken(FixedToken::LeftBrace)) = parse_context.tokens().peek() else {
+            parse_context.record_error(ParseErrorKind::UnexpectedToken);
+            return Err(ShouldSynchronize::Yes);
+        };
+
+        Ok(Self {
+            name,
+            parameters,
+            body: Block::parse(parse_context)?.statements,
+        })
+    }
+}
+
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(fun")?;
+
+        if let Some(name) = &self.name {
+            write!(f, " {name}")?;
+        }
+
+        write!(f, " (")?;
+
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{parameter}")?;
         }
+
+        write!(f, ")")?;
+
+        for declaration in &self.body {
+            write!(f, " {declaration}")?;
+        }
+
+        write!(f, ")")
     }
 }
 
@@ -104,15 +202,52 @@ impl VariableDeclaration {
 
 impl std::fmt::Display for Declaration {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Declaration::VariableDeclaration(variable_declaration) => {
+        match &self.kind {
+            DeclarationKind::VariableDeclaration(variable_declaration) => {
                 write!(
                     f,
                     "(declare-variable {} {:?})",
                     variable_declaration.identifier, variable_declaration.value
                 )
             }
-            Declaration::Statement(statement) => statement.fmt(f),
+            DeclarationKind::FunctionDeclaration(function) => function.fmt(f),
+            DeclarationKind::Statement(statement) => statement.fmt(f),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let code =
+            "var a;\n\nfun f() {\n    print a;\n}\nprint 1; print 2;\nvar g = () =>\n    a;\n";
+        let tokens = lexer::tokenize(code).unwrap();
+        let ast = Ast::new(tokens.into_iter()).unwrap();
+
+        let lines: Vec<usize> = ast
+            .program
+            .declarations
+            .iter()
+            .map(|declaration| declaration.line)
+            .collect();
+        assert_eq!(lines, [1, 3, 6, 6, 7]);
+
+        let DeclarationKind::FunctionDeclaration(function) = &ast.program.declarations[1].kind
+        else {
+            panic!("expected a function declaration");
+        };
+        assert_eq!(function.body[0].line, 4);
+
+        let DeclarationKind::VariableDeclaration(VariableDeclaration {
+            value: Some(Expression::Primary(Primary::Function(function))),
+            ..
+        }) = &ast.program.declarations[4].kind
+        else {
+            panic!("expected an arrow function");
+        };
+        assert_eq!(function.body[0].line, 8);
+    }
+}
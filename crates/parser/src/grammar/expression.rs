@@ -1,8 +1,10 @@
-use lexer::{tokens::FixedToken, Token};
+use lexer::{Symbol, Token, tokens::FixedToken};
 
 use crate::grammar::*;
 use crate::parser::*;
 
+use std::rc::Rc;
+
 impl Expression {
     pub fn parse<T: Iterator<Item = Token>>(
         parse_context: &mut ParseContext<T>,
@@ -20,23 +22,71 @@ fn expression<T: Iterator<Item = Token>>(
 fn assignment<T: Iterator<Item = Token>>(
     parse_context: &mut ParseContext<T>,
 ) -> ParseResult<Expression> {
-    let expr = logical_or(parse_context)?;
+    let expr = conditional(parse_context)?;
 
-    if let Expression::Primary(Primary::Identifier(identifier)) = &expr {
-        match parse_context.tokens().peek().expect("Expected tokens") {
-            Token::FixedToken(FixedToken::Equal) => {
-                parse_context.tokens().next();
-                let value = Expression::parse(parse_context)?;
-                return Ok(Expression::Assignment {
-                    identifier: identifier.clone(),
-                    value: Box::new(value),
-                });
-            }
-            _ => (),
+    let Some(Token::FixedToken(token)) = parse_context.tokens().peek() else {
+        return Ok(expr);
+    };
+
+    let operator = match token {
+        FixedToken::Equal => None,
+        FixedToken::PlusEqual => Some(BinaryOperator::Addition),
+        FixedToken::MinusEqual => Some(BinaryOperator::Subtraction),
+        FixedToken::AsteriskEqual => Some(BinaryOperator::Multiplication),
+        FixedToken::ForwardSlashEqual => Some(BinaryOperator::Division),
+        FixedToken::PercentEqual => Some(BinaryOperator::Modulo),
+        _ => return Ok(expr),
+    };
+
+    parse_context.tokens().next();
+
+    let target = assignment_target(parse_context, expr)?;
+    let value = Expression::parse(parse_context)?;
+
+    Ok(Expression::Assignment(Assignment {
+        target,
+        operator,
+        value: Box::new(value),
+    }))
+}
+
+fn assignment_target<T: Iterator<Item = Token>>(
+    parse_context: &mut ParseContext<T>,
+    expr: Expression,
+) -> ParseResult<AssignmentTarget> {
+    match expr {
+        Expression::Primary(Primary::Identifier(identifier)) => {
+            Ok(AssignmentTarget::Identifier(identifier))
+        }
+        Expression::Property(property) => Ok(AssignmentTarget::Property(property)),
+        Expression::Index(index) => Ok(AssignmentTarget::Index(index)),
+        _ => {
+            parse_context.record_error(ParseErrorKind::InvalidAssignmentTarget);
+            Err(ShouldSynchronize::Yes)
         }
     }
+}
+
+fn conditional<T: Iterator<Item = Token>>(
+    parse_context: &mut ParseContext<T>,
+) -> ParseResult<Expression> {
+    let condition = logical_or(parse_context)?;
+
+    let Some(Token::FixedToken(FixedToken::Question)) = parse_context.tokens().peek() else {
+        return Ok(condition);
+    };
+
+    parse_context.tokens().next();
+
+    let then = expression(parse_context)?;
+    parse_context.match_token(FixedToken::Colon)?;
+    let else_ = conditional(parse_context)?;
 
-    Ok(expr)
+    Ok(Expression::Conditional(Conditional {
+        condition: Box::new(condition),
+        then: Box::new(then),
+        else_: Box::new(else_),
+    }))
 }
 
 fn logical_or<T: Iterator<Item = Token>>(
@@ -189,6 +239,8 @@ fn factor<T: Iterator<Item = Token>>(
         let operator = match token {
             FixedToken::Asterisk => BinaryOperator::Multiplication,
             FixedToken::ForwardSlash => BinaryOperator::Division,
+            FixedToken::ForwardSlashForwardSlash => BinaryOperator::IntegerDivision,
+            FixedToken::Percent => BinaryOperator::Modulo,
             _ => return Ok(expression),
         };
 
@@ -214,7 +266,13 @@ fn unary<T: Iterator<Item = Token>>(
     {
         Token::FixedToken(FixedToken::Minus) => UnaryOperator::Negate,
         Token::FixedToken(FixedToken::Bang) => UnaryOperator::Not,
-        _ => return call(parse_context),
+        Token::FixedToken(FixedToken::PlusPlus) => {
+            return increment(parse_context, IncrementOperator::Increment);
+        }
+        Token::FixedToken(FixedToken::MinusMinus) => {
+            return increment(parse_context, IncrementOperator::Decrement);
+        }
+        _ => return exponent(parse_context),
     };
 
     parse_context.tokens().next();
@@ -225,26 +283,122 @@ fn unary<T: Iterator<Item = Token>>(
     }))
 }
 
-fn call<T: Iterator<Item = Token>>(parse_context: &mut ParseContext<T>) -> ParseResult<Expression> {
-    let callable = Primary::parse(parse_context)?;
+fn increment<T: Iterator<Item = Token>>(
+    parse_context: &mut ParseContext<T>,
+    operator: IncrementOperator,
+) -> ParseResult<Expression> {
+    parse_context.tokens().next();
+
+    let expr = unary(parse_context)?;
+
+    Ok(Expression::Increment(Increment {
+        target: assignment_target(parse_context, expr)?,
+        operator,
+        fixity: Fixity::Prefix,
+    }))
+}
 
-    let Some(Token::FixedToken(FixedToken::LeftParenthesis)) = parse_context.tokens().peek() else {
-        return Ok(Expression::Primary(callable));
+fn exponent<T: Iterator<Item = Token>>(
+    parse_context: &mut ParseContext<T>,
+) -> ParseResult<Expression> {
+    let expression = call(parse_context)?;
+
+    let Some(Token::FixedToken(FixedToken::AsteriskAsterisk)) = parse_context.tokens().peek()
+    else {
+        return Ok(expression);
     };
 
     parse_context.tokens().next();
 
+    /* the right operand recurses through unary, making ** right-associative */
+    let right = unary(parse_context)?;
+
+    Ok(Expression::Binary(Binary {
+        left: Box::new(expression),
+        operator: BinaryOperator::Exponentiation,
+        right: Box::new(right),
+    }))
+}
+
+fn call<T: Iterator<Item = Token>>(parse_context: &mut ParseContext<T>) -> ParseResult<Expression> {
+    let mut expr = Expression::Primary(Primary::parse(parse_context)?);
+
+    loop {
+        let Some(Token::FixedToken(token)) = parse_context.tokens().peek() else {
+            return Ok(expr);
+        };
+
+        expr = match token {
+            FixedToken::LeftParenthesis => {
+                parse_context.tokens().next();
+
+                Expression::Call(Call {
+                    callee: Box::new(expr),
+                    arguments: arguments(parse_context)?,
+                })
+            }
+            FixedToken::Dot => {
+                parse_context.tokens().next();
+
+                let Some(Token::Identifier(identifier)) = parse_context.tokens().next() else {
+                    parse_context.record_error(ParseErrorKind::ExpectedIdentifier);
+                    return Err(ShouldSynchronize::Yes);
+                };
+
+                Expression::Property(Property {
+                    object: Box::new(expr),
+                    name: identifier.name,
+                })
+            }
+            FixedToken::LeftBracket => {
+                parse_context.tokens().next();
+
+                let index = expression(parse_context)?;
+                parse_context.match_token(FixedToken::RightBracket)?;
+
+                Expression::Index(Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                })
+            }
+            FixedToken::PlusPlus | FixedToken::MinusMinus => {
+                let operator = match token {
+                    FixedToken::PlusPlus => IncrementOperator::Increment,
+                    _ => IncrementOperator::Decrement,
+                };
+
+                parse_context.tokens().next();
+
+                /* the result is not an assignment target, so nothing may follow it */
+                return Ok(Expression::Increment(Increment {
+                    target: assignment_target(parse_context, expr)?,
+                    operator,
+                    fixity: Fixity::Postfix,
+                }));
+            }
+            _ => return Ok(expr),
+        };
+    }
+}
+
+fn arguments<T: Iterator<Item = Token>>(
+    parse_context: &mut ParseContext<T>,
+) -> ParseResult<Vec<Expression>> {
     let mut arguments: Vec<Expression> = Vec::new();
 
     if let Some(Token::FixedToken(FixedToken::RightParenthesis)) = parse_context.tokens().peek() {
         parse_context.tokens().next();
-        return Ok(Expression::Primary(Primary::Call {
-            callable: Box::new(callable),
-            arguments,
-        }));
+        return Ok(arguments);
     };
 
+    let mut too_many_arguments = false;
+
     loop {
+        if arguments.len() == MAX_ARGUMENTS {
+            parse_context.record_error(ParseErrorKind::TooManyArguments);
+            too_many_arguments = true;
+        }
+
         arguments.push(expression(parse_context)?);
 
         match parse_context.tokens().next() {
@@ -257,10 +411,55 @@ fn call<T: Iterator<Item = Token>>(parse_context: &mut ParseContext<T>) -> Parse
         }
     }
 
-    Ok(Expression::Primary(Primary::Call {
-        callable: Box::new(callable),
-        arguments,
-    }))
+    if too_many_arguments {
+        return Err(ShouldSynchronize::Yes);
+    }
+
+    Ok(arguments)
+}
+
+fn arrow_parameter<T: Iterator<Item = Token>>(
+    parse_context: &mut ParseContext<T>,
+    expr: Expression,
+) -> ParseResult<Symbol> {
+    match expr {
+        Expression::Primary(Primary::Identifier(variable)) => Ok(variable.name),
+        _ => {
+            parse_context.record_error(ParseErrorKind::ExpectedIdentifier);
+            Err(ShouldSynchronize::Yes)
+        }
+    }
+}
+
+/* parses the `=> body` of an arrow function whose parameter list has been consumed */
+fn arrow_function<T: Iterator<Item = Token>>(
+    parse_context: &mut ParseContext<T>,
+    parameters: Vec<Symbol>,
+) -> ParseResult<Primary> {
+    parse_context.match_token(FixedToken::EqualGreater)?;
+
+    if parameters.len() > MAX_ARGUMENTS {
+        parse_context.record_error(ParseErrorKind::TooManyParameters);
+        return Err(ShouldSynchronize::Yes);
+    }
+
+    /* as in javascript, a brace after the arrow begins a block body rather than a map */
+    let line = parse_context.line();
+    let body = match parse_context.tokens().peek() {
+        Some(Token::FixedToken(FixedToken::LeftBrace)) => Block::parse(parse_context)?.statements,
+        _ => vec![Declaration {
+            kind: DeclarationKind::Statement(Statement::ReturnStatement(Some(expression(
+                parse_context,
+            )?))),
+            line,
+        }],
+    };
+
+    Ok(Primary::Function(Rc::new(Function {
+        name: None,
+        parameters,
+        body,
+    })))
 }
 
 impl Primary {
@@ -276,24 +475,128 @@ impl Primary {
             Token::FixedToken(FixedToken::False) => Ok(Primary::False),
             Token::FixedToken(FixedToken::Nil) => Ok(Primary::Nil),
             Token::NumericLiteral(literal) => Ok(Primary::Number(literal.value)),
-            Token::StringLiteral(literal) => Ok(Primary::String_(literal.value.clone())),
-            Token::Identifier(identifier) => Ok(Primary::Identifier(identifier.name)),
+            Token::StringLiteral(literal) => {
+                Ok(Primary::String_(parse_context.string(literal.value)))
+            }
+            Token::Identifier(identifier) => match parse_context.tokens().peek() {
+                /* a lone parameter needs no parentheses */
+                Some(Token::FixedToken(FixedToken::EqualGreater)) => {
+                    arrow_function(parse_context, vec![identifier.name])
+                }
+                _ => Ok(Primary::Identifier(Variable::new(identifier.name))),
+            },
             Token::FixedToken(FixedToken::LeftParenthesis) => {
+                /* empty parentheses can only begin an arrow function */
+                if let Some(Token::FixedToken(FixedToken::RightParenthesis)) =
+                    parse_context.tokens().peek()
+                {
+                    parse_context.tokens().next();
+                    return arrow_function(parse_context, Vec::new());
+                }
+
                 let expression = expression(parse_context)?;
 
                 match parse_context.tokens().next() {
-                    Some(token) => match token {
-                        Token::FixedToken(FixedToken::RightParenthesis) => {
-                            Ok(Primary::Grouping(Box::new(expression)))
+                    Some(Token::FixedToken(FixedToken::RightParenthesis)) => {
+                        match parse_context.tokens().peek() {
+                            Some(Token::FixedToken(FixedToken::EqualGreater)) => {
+                                let parameter = arrow_parameter(parse_context, expression)?;
+                                arrow_function(parse_context, vec![parameter])
+                            }
+                            _ => Ok(Primary::Grouping(Box::new(expression))),
+                        }
+                    }
+                    /* so can a comma directly inside parentheses */
+                    Some(Token::FixedToken(FixedToken::Comma)) => {
+                        let mut parameters = vec![arrow_parameter(parse_context, expression)?];
+
+                        loop {
+                            let Some(Token::Identifier(identifier)) = parse_context.tokens().next()
+                            else {
+                                parse_context.record_error(ParseErrorKind::ExpectedIdentifier);
+                                return Err(ShouldSynchronize::Yes);
+                            };
+
+                            parameters.push(identifier.name);
+
+                            match parse_context.tokens().next() {
+                                Some(Token::FixedToken(FixedToken::Comma)) => (),
+                                Some(Token::FixedToken(FixedToken::RightParenthesis)) => break,
+                                _ => {
+                                    parse_context.record_error(ParseErrorKind::UnexpectedToken);
+                                    return Err(ShouldSynchronize::Yes);
+                                }
+                            }
                         }
-                        _ => panic!("Expected right parenthesis, instead got {:?}", token),
-                    },
-                    None => {
+
+                        arrow_function(parse_context, parameters)
+                    }
+                    _ => {
                         parse_context.record_error(ParseErrorKind::UnmatchedParenthesis);
                         Err(ShouldSynchronize::Yes)
                     }
                 }
             }
+            Token::FixedToken(FixedToken::Fun) => Ok(Primary::Function(Rc::new(Function::parse(
+                parse_context,
+                None,
+            )?))),
+            Token::FixedToken(FixedToken::LeftBracket) => {
+                let mut elements: Vec<Expression> = Vec::new();
+
+                if let Some(Token::FixedToken(FixedToken::RightBracket)) =
+                    parse_context.tokens().peek()
+                {
+                    parse_context.tokens().next();
+                    return Ok(Primary::List(elements));
+                }
+
+                loop {
+                    elements.push(expression(parse_context)?);
+
+                    match parse_context.tokens().next() {
+                        Some(Token::FixedToken(FixedToken::Comma)) => (),
+                        Some(Token::FixedToken(FixedToken::RightBracket)) => break,
+                        _ => {
+                            parse_context.record_error(ParseErrorKind::UnexpectedToken);
+                            return Err(ShouldSynchronize::Yes);
+                        }
+                    }
+                }
+
+                Ok(Primary::List(elements))
+            }
+            /* a statement starting with a brace is always a block, so a map literal is only ever
+             * reached in expression position */
+            Token::FixedToken(FixedToken::LeftBrace) => {
+                let mut entries: Vec<(Expression, Expression)> = Vec::new();
+
+                if let Some(Token::FixedToken(FixedToken::RightBrace)) =
+                    parse_context.tokens().peek()
+                {
+                    parse_context.tokens().next();
+                    return Ok(Primary::Map(entries));
+                }
+
+                loop {
+                    let key = expression(parse_context)?;
+                    parse_context.match_token(FixedToken::Colon)?;
+                    let value = expression(parse_context)?;
+
+                    entries.push((key, value));
+
+                    match parse_context.tokens().next() {
+                        Some(Token::FixedToken(FixedToken::Comma)) => (),
+                        Some(Token::FixedToken(FixedToken::RightBrace)) => break,
+                        _ => {
+                            parse_context.record_error(ParseErrorKind::UnexpectedToken);
+                            return Err(ShouldSynchronize::Yes);
+                        }
+                    }
+                }
+
+                Ok(Primary::Map(entries))
+            }
             _ => {
                 parse_context.record_error(ParseErrorKind::ExpectedPrimaryExpression);
                 Err(ShouldSynchronize::Yes)
@@ -307,9 +610,34 @@ use std::fmt;
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Expression::Assignment { identifier, value } => {
-                write!(f, "(assign {identifier} {value})")
+            Expression::Assignment(assignment) => match &assignment.operator {
+                Some(operator) => write!(
+                    f,
+                    "({operator}= {} {})",
+                    assignment.target, assignment.value
+                ),
+                None => write!(f, "(assign {} {})", assignment.target, assignment.value),
+            },
+            Expression::Increment(increment) => match increment.fixity {
+                Fixity::Prefix => write!(f, "(pre{} {})", increment.operator, increment.target),
+                Fixity::Postfix => write!(f, "(post{} {})", increment.operator, increment.target),
+            },
+            Expression::Conditional(conditional) => write!(
+                f,
+                "(?: {} {} {})",
+                conditional.condition, conditional.then, conditional.else_
+            ),
+            Expression::Call(call) => {
+                write!(f, "(call {}", call.callee)?;
+
+                for argument in &call.arguments {
+                    write!(f, " {argument}")?;
+                }
+
+                write!(f, ")")
             }
+            Expression::Property(property) => write!(f, "{property}"),
+            Expression::Index(index) => write!(f, "{index}"),
             Expression::Primary(value) => write!(f, "{}", value),
             Expression::Unary(unary) => {
                 write!(f, "({} {})", unary.operator, unary.right)
@@ -321,6 +649,37 @@ impl fmt::Display for Expression {
     }
 }
 
+impl fmt::Display for AssignmentTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssignmentTarget::Identifier(variable) => write!(f, "{}", variable.name),
+            AssignmentTarget::Property(property) => write!(f, "{property}"),
+            AssignmentTarget::Index(index) => write!(f, "{index}"),
+        }
+    }
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(. {} {})", self.object, self.name)
+    }
+}
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "([] {} {})", self.object, self.index)
+    }
+}
+
+impl fmt::Display for IncrementOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncrementOperator::Increment => write!(f, "++"),
+            IncrementOperator::Decrement => write!(f, "--"),
+        }
+    }
+}
+
 impl fmt::Display for BinaryOperator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -336,6 +695,9 @@ impl fmt::Display for BinaryOperator {
             BinaryOperator::Subtraction => write!(f, "-"),
             BinaryOperator::Multiplication => write!(f, "*"),
             BinaryOperator::Division => write!(f, "/"),
+            BinaryOperator::IntegerDivision => write!(f, "//"),
+            BinaryOperator::Modulo => write!(f, "%"),
+            BinaryOperator::Exponentiation => write!(f, "**"),
         }
     }
 }
@@ -352,17 +714,265 @@ impl fmt::Display for UnaryOperator {
 impl fmt::Display for Primary {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Primary::Call {
-                callable,
-                arguments,
-            } => write!(f, "({callable:?} {arguments:?})"),
             Primary::True => write!(f, "true"),
             Primary::False => write!(f, "false"),
             Primary::Nil => write!(f, "nil"),
             Primary::Number(value) => write!(f, "{value:?}"),
-            Primary::Identifier(name) => write!(f, "{name}"),
+            Primary::Identifier(variable) => write!(f, "{}", variable.name),
             Primary::String_(value) => write!(f, "{value:?}"),
-            Primary::Grouping(expression) => write!(f, "{expression:?}"),
+            Primary::Grouping(expression) => write!(f, "(group {expression})"),
+            Primary::List(elements) => {
+                write!(f, "(list")?;
+
+                for element in elements {
+                    write!(f, " {element}")?;
+                }
+
+                write!(f, ")")
+            }
+            Primary::Function(function) => write!(f, "{function}"),
+            Primary::Map(entries) => {
+                write!(f, "(map")?;
+
+                for (key, value) in entries {
+                    write!(f, " ({key} {value})")?;
+                }
+
+                write!(f, ")")
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(code: &str) -> Result<String, Vec<ParseErrorKind>> {
+        let tokens = lexer::tokenize(code).unwrap();
+
+        Ast::new(tokens.into_iter())
+            .map(|ast| ast.to_string().trim_end().to_string())
+            .map_err(|errors| errors.error_kinds().collect())
+    }
+
+    #[test]
+    fn test_equal_strings_share_storage() {
+        let tokens = lexer::tokenize("print \"ab\"; print \"ab\";").unwrap();
+        let ast = Ast::new(tokens.into_iter()).unwrap();
+
+        let strings: Vec<&Rc<str>> = ast
+            .program
+            .declarations
+            .iter()
+            .map(|declaration| match &declaration.kind {
+                DeclarationKind::Statement(Statement::PrintStatement(Expression::Primary(
+                    Primary::String_(value),
+                ))) => value,
+                other => panic!("expected a printed string, got {other:?}"),
+            })
+            .collect();
+
+        assert!(Rc::ptr_eq(strings[0], strings[1]));
+    }
+
+    #[test]
+    fn test_chained_calls() {
+        assert_eq!(
+            parse("makeAdder(1)(2);").unwrap(),
+            "(call (call makeAdder 1.0) 2.0)"
+        );
+        assert_eq!(parse("f()();").unwrap(), "(call (call f))");
+    }
+
+    #[test]
+    fn test_postfix_chain() {
+        assert_eq!(
+            parse("obj.method().other[0](x, y);").unwrap(),
+            "(call ([] (. (call (. obj method)) other) 0.0) x y)"
+        );
+    }
+
+    #[test]
+    fn test_postfix_binds_tighter_than_unary() {
+        assert_eq!(parse("-f(1);").unwrap(), "(- (call f 1.0))");
+    }
+
+    #[test]
+    fn test_conditional_is_right_associative() {
+        assert_eq!(parse("a ? b : c ? d : e;").unwrap(), "(?: a b (?: c d e))");
+        assert_eq!(parse("a ? b ? c : d : e;").unwrap(), "(?: a (?: b c d) e)");
+    }
+
+    #[test]
+    fn test_conditional_precedence() {
+        assert_eq!(
+            parse("x = a or b ? 1 + 2 : 3;").unwrap(),
+            "(assign x (?: (or a b) (+ 1.0 2.0) 3.0))"
+        );
+    }
+
+    #[test]
+    fn test_conditional_missing_colon() {
+        assert!(parse("a ? b;").is_err());
+    }
+
+    #[test]
+    fn test_exponent_is_right_associative() {
+        assert_eq!(parse("2 ** 3 ** 2;").unwrap(), "(** 2.0 (** 3.0 2.0))");
+    }
+
+    #[test]
+    fn test_exponent_precedence() {
+        assert_eq!(parse("-a ** 2;").unwrap(), "(- (** a 2.0))");
+        assert_eq!(parse("a ** -b;").unwrap(), "(** a (- b))");
+        assert_eq!(parse("2 * 3 ** 2;").unwrap(), "(* 2.0 (** 3.0 2.0))");
+    }
+
+    #[test]
+    fn test_factor_operators() {
+        assert_eq!(parse("a % b // c / d;").unwrap(), "(/ (// (% a b) c) d)");
+        assert_eq!(parse("a + b % c;").unwrap(), "(+ a (% b c))");
+    }
+
+    #[test]
+    fn test_assignment_targets() {
+        assert_eq!(parse("x = 1;").unwrap(), "(assign x 1.0)");
+        assert_eq!(parse("a.b = 1;").unwrap(), "(assign (. a b) 1.0)");
+        assert_eq!(parse("a[i] = 1;").unwrap(), "(assign ([] a i) 1.0)");
+        assert_eq!(parse("x = y = 1;").unwrap(), "(assign x (assign y 1.0))");
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        assert_eq!(parse("x += 1;").unwrap(), "(+= x 1.0)");
+        assert_eq!(parse("a.b -= 2;").unwrap(), "(-= (. a b) 2.0)");
+        assert_eq!(parse("a[f()] *= 3;").unwrap(), "(*= ([] a (call f)) 3.0)");
+        assert_eq!(parse("x /= y %= 2;").unwrap(), "(/= x (%= y 2.0))");
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        assert!(matches!(
+            parse("a + b = 1;").unwrap_err()[..],
+            [ParseErrorKind::InvalidAssignmentTarget]
+        ));
+        assert!(matches!(
+            parse("f() += 1;").unwrap_err()[..],
+            [ParseErrorKind::InvalidAssignmentTarget]
+        ));
+        assert!(matches!(
+            parse("++x++;").unwrap_err()[..],
+            [ParseErrorKind::InvalidAssignmentTarget]
+        ));
+    }
+
+    #[test]
+    fn test_increment() {
+        assert_eq!(parse("++x;").unwrap(), "(pre++ x)");
+        assert_eq!(parse("x--;").unwrap(), "(post-- x)");
+        assert_eq!(parse("-a[0]++;").unwrap(), "(- (post++ ([] a 0.0)))");
+        assert_eq!(parse("--a.b;").unwrap(), "(pre-- (. a b))");
+    }
+
+    #[test]
+    fn test_list_literal() {
+        assert_eq!(parse("[];").unwrap(), "(list)");
+        assert_eq!(
+            parse("[1, [2], a + b];").unwrap(),
+            "(list 1.0 (list 2.0) (+ a b))"
+        );
+        assert_eq!(parse("[1, 2][0];").unwrap(), "([] (list 1.0 2.0) 0.0)");
+        assert!(parse("[1, 2;").is_err());
+    }
+
+    #[test]
+    fn test_map_literal() {
+        assert_eq!(parse("x = {};").unwrap(), "(assign x (map))");
+        assert_eq!(
+            parse("x = {\"a\": 1, b: c ? d : e};").unwrap(),
+            "(assign x (map (\"a\" 1.0) (b (?: c d e))))"
+        );
+        assert!(parse("{\"a\": 1}[\"a\"];").is_err());
+        assert!(parse("x = {1 2};").is_err());
+    }
+
+    #[test]
+    fn test_brace_statement_is_block() {
+        assert_eq!(parse("{}").unwrap(), "(block Block { statements: [] })");
+    }
+
+    #[test]
+    fn test_function_expression() {
+        assert_eq!(
+            parse("f = fun (a, b) { return a + b; };").unwrap(),
+            "(assign f (fun (a b) (return (+ a b))))"
+        );
+        assert_eq!(parse("f(fun () {});").unwrap(), "(call f (fun ()))");
+    }
+
+    #[test]
+    fn test_arrow_function() {
+        assert_eq!(parse("() => 1;").unwrap(), "(fun () (return 1.0))");
+        assert_eq!(parse("(a) => a;").unwrap(), "(fun (a) (return a))");
+        assert_eq!(parse("a => a;").unwrap(), "(fun (a) (return a))");
+        assert_eq!(
+            parse("map(xs, (a, b) => a + b);").unwrap(),
+            "(call map xs (fun (a b) (return (+ a b))))"
+        );
+        assert_eq!(
+            parse("f = (a) => { print a; };").unwrap(),
+            "(assign f (fun (a) (print a)))"
+        );
+        assert_eq!(
+            parse("f = (a) => (b) => a + b;").unwrap(),
+            "(assign f (fun (a) (return (fun (b) (return (+ a b))))))"
+        );
+    }
+
+    #[test]
+    fn test_grouping_is_not_arrow_function() {
+        assert_eq!(parse("(a);").unwrap(), "(group a)");
+        assert!(parse("(a + 1) => a;").is_err());
+        assert!(parse("(a, 1) => a;").is_err());
+        assert!(parse("(a, b);").is_err());
+    }
+
+    #[test]
+    fn test_function_declaration() {
+        assert_eq!(
+            parse("fun add(a, b) { return a + b; }").unwrap(),
+            "(fun add (a b) (return (+ a b)))"
+        );
+        assert_eq!(parse("fun f() { return; }").unwrap(), "(fun f () (return))");
+        assert!(parse("fun (a) { return a; }").is_err());
+    }
+
+    #[test]
+    fn test_too_many_parameters() {
+        let parameters = (0..=MAX_ARGUMENTS)
+            .map(|i| format!("p{i}"))
+            .collect::<Vec<_>>();
+
+        assert!(matches!(
+            parse(&format!("f = fun ({}) {{}};", parameters.join(", "))).unwrap_err()[..],
+            [ParseErrorKind::TooManyParameters]
+        ));
+        assert!(matches!(
+            parse(&format!("f = ({}) => 1;", parameters.join(", "))).unwrap_err()[..],
+            [ParseErrorKind::TooManyParameters]
+        ));
+    }
+
+    #[test]
+    fn test_too_many_arguments() {
+        let arguments = vec!["1"; MAX_ARGUMENTS];
+        assert!(parse(&format!("f({});", arguments.join(", "))).is_ok());
+
+        let arguments = vec!["1"; MAX_ARGUMENTS + 1];
+        assert!(matches!(
+            parse(&format!("f({});", arguments.join(", "))).unwrap_err()[..],
+            [ParseErrorKind::TooManyArguments]
+        ));
+    }
+}
@@ -12,7 +12,9 @@ impl Statement {
 }
 
 impl Block {
-    fn parse<T: Iterator<Item = Token>>(parse_context: &mut ParseContext<T>) -> ParseResult<Self> {
+    pub fn parse<T: Iterator<Item = Token>>(
+        parse_context: &mut ParseContext<T>,
+    ) -> ParseResult<Self> {
         parse_context.tokens().next();
 
         let mut declarations: Vec<Declaration> = Vec::new();
@@ -43,7 +45,7 @@ impl ForLoopInitializer {
             _ => {
                 parse_context.tokens().next().unwrap();
                 parse_context.record_error(ParseErrorKind::UnexpectedToken);
-                return Err(ShouldSynchronize::Yes);
+                Err(ShouldSynchronize::Yes)
             }
         }
     }
@@ -62,26 +64,25 @@ fn statement<T: Iterator<Item = Token>>(
 
             let then = Statement::parse(parse_context)?;
 
-            let else_ = match parse_context.tokens().next() {
-                Some(Token::FixedToken(FixedToken::Else)) => Some(Statement::parse(parse_context)?),
+            let else_ = match parse_context.tokens().peek() {
+                Some(Token::FixedToken(FixedToken::Else)) => {
+                    parse_context.tokens().next();
+                    Some(Statement::parse(parse_context)?)
+                }
                 _ => None,
             };
 
-            return Ok(Statement::IfStatement {
+            Ok(Statement::IfStatement {
                 condition,
                 then: Box::new(then),
                 else_: else_.map(Box::new),
-            });
+            })
         }
         Token::FixedToken(FixedToken::For) => {
             parse_context.tokens().next();
 
-            println!("token: {:?}", parse_context.tokens().peek());
-
             parse_context.match_token(FixedToken::LeftParenthesis)?;
 
-            println!("token: {:?}", parse_context.tokens().peek());
-
             let initializer = ForLoopInitializer::parse(parse_context)?;
 
             let condition = match parse_context.tokens().peek() {
@@ -100,12 +101,12 @@ fn statement<T: Iterator<Item = Token>>(
 
             let body = Statement::parse(parse_context)?;
 
-            return Ok(Statement::ForStatement {
+            Ok(Statement::ForStatement {
                 initializer,
                 condition,
                 expression,
                 body: Box::new(body),
-            });
+            })
         }
         Token::FixedToken(FixedToken::Print) => {
             parse_context.tokens().next();
@@ -119,6 +120,24 @@ fn statement<T: Iterator<Item = Token>>(
                 }
             }
         }
+        Token::FixedToken(FixedToken::Return) => {
+            parse_context.tokens().next();
+
+            let value = match parse_context.tokens().peek() {
+                Some(Token::FixedToken(FixedToken::Semicolon)) => None,
+                _ => Some(Expression::parse(parse_context)?),
+            };
+
+            match parse_context.tokens().next() {
+                Some(Token::FixedToken(FixedToken::Semicolon)) => {
+                    Ok(Statement::ReturnStatement(value))
+                }
+                _ => {
+                    parse_context.record_error(ParseErrorKind::ExpectedSemicolon);
+                    Err(ShouldSynchronize::Yes)
+                }
+            }
+        }
         Token::FixedToken(FixedToken::While) => {
             parse_context.tokens().next();
             parse_context.match_token(FixedToken::LeftParenthesis)?;
@@ -129,10 +148,10 @@ fn statement<T: Iterator<Item = Token>>(
 
             let body = Statement::parse(parse_context)?;
 
-            return Ok(Statement::WhileStatement {
+            Ok(Statement::WhileStatement {
                 condition,
                 body: Box::new(body),
-            });
+            })
         }
         Token::FixedToken(FixedToken::LeftBrace) => {
             Block::parse(parse_context).map(Statement::Block)
@@ -171,6 +190,8 @@ impl fmt::Display for Statement {
                 else_,
             } => write!(f, "(if {condition} {then} {else_:?})"),
             Statement::PrintStatement(expression) => write!(f, "(print {})", expression),
+            Statement::ReturnStatement(Some(value)) => write!(f, "(return {value})"),
+            Statement::ReturnStatement(None) => write!(f, "(return)"),
             Statement::WhileStatement { condition, body } => {
                 write!(f, "(while {condition} {body})")
             }
@@ -1,4 +1,7 @@
-mod parser;
 pub mod grammar;
+mod optimizer;
+mod parser;
+mod resolver;
 
-pub use parser::Ast;
+pub use parser::{Ast, ParseErrorKind};
+pub use resolver::ResolveError;
@@ -0,0 +1,440 @@
+use crate::Ast;
+use crate::grammar::*;
+
+use std::rc::Rc;
+
+impl Ast {
+    /// Folds the expressions whose operands are constants, and removes the statements that can
+    /// never run: the branch that an `if` with a constant condition doesn't take, and whatever
+    /// follows a `return` in the same block. This should run after `resolve`, so that errors in
+    /// the removed code are still reported.
+    pub fn optimize(&mut self) {
+        optimize_declarations(&mut self.program.declarations);
+    }
+}
+
+fn optimize_declarations(declarations: &mut Vec<Declaration>) {
+    let mut optimized = Vec::with_capacity(declarations.len());
+
+    for mut declaration in declarations.drain(..) {
+        match &mut declaration.kind {
+            DeclarationKind::VariableDeclaration(variable_declaration) => {
+                optimize_variable_declaration(variable_declaration)
+            }
+            DeclarationKind::FunctionDeclaration(function) => optimize_function(function),
+            DeclarationKind::Statement(statement) => {
+                let Some(kept) = optimize_statement(take_statement(statement)) else {
+                    continue;
+                };
+
+                *statement = kept;
+            }
+        }
+
+        let returns = matches!(
+            declaration.kind,
+            DeclarationKind::Statement(Statement::ReturnStatement(_))
+        );
+        optimized.push(declaration);
+
+        if returns {
+            break;
+        }
+    }
+
+    *declarations = optimized;
+}
+
+fn optimize_variable_declaration(variable_declaration: &mut VariableDeclaration) {
+    if let Some(value) = &mut variable_declaration.value {
+        optimize_expression(value);
+    }
+}
+
+fn optimize_function(function: &mut Rc<Function>) {
+    let function = Rc::get_mut(function).expect("functions are optimized before they are shared");
+
+    optimize_declarations(&mut function.body);
+}
+
+/* None when the statement can never run, and so can be left out */
+fn optimize_statement(statement: Statement) -> Option<Statement> {
+    match statement {
+        Statement::ExpressionStatement(mut expression) => {
+            optimize_expression(&mut expression);
+            Some(Statement::ExpressionStatement(expression))
+        }
+        Statement::ForStatement {
+            mut initializer,
+            mut condition,
+            mut expression,
+            body,
+        } => {
+            match &mut initializer {
+                ForLoopInitializer::Declaration(variable_declaration) => {
+                    optimize_variable_declaration(variable_declaration)
+                }
+            }
+
+            for expression in condition.iter_mut().chain(expression.iter_mut()) {
+                optimize_expression(expression);
+            }
+
+            Some(Statement::ForStatement {
+                initializer,
+                condition,
+                expression,
+                body: Box::new(optimize_body(*body)),
+            })
+        }
+        Statement::IfStatement {
+            mut condition,
+            then,
+            else_,
+        } => {
+            optimize_expression(&mut condition);
+
+            match truthiness(&condition) {
+                Some(true) => optimize_statement(*then),
+                Some(false) => else_.and_then(|else_| optimize_statement(*else_)),
+                None => Some(Statement::IfStatement {
+                    condition,
+                    then: Box::new(optimize_body(*then)),
+                    else_: else_
+                        .and_then(|else_| optimize_statement(*else_))
+                        .map(Box::new),
+                }),
+            }
+        }
+        Statement::PrintStatement(mut expression) => {
+            optimize_expression(&mut expression);
+            Some(Statement::PrintStatement(expression))
+        }
+        Statement::ReturnStatement(mut value) => {
+            if let Some(value) = &mut value {
+                optimize_expression(value);
+            }
+
+            Some(Statement::ReturnStatement(value))
+        }
+        Statement::WhileStatement {
+            mut condition,
+            body,
+        } => {
+            optimize_expression(&mut condition);
+
+            Some(Statement::WhileStatement {
+                condition,
+                body: Box::new(optimize_body(*body)),
+            })
+        }
+        Statement::Block(mut block) => {
+            optimize_declarations(&mut block.statements);
+            Some(Statement::Block(block))
+        }
+    }
+}
+
+/* a statement that something else needs, which an empty block stands in for once removed */
+fn optimize_body(statement: Statement) -> Statement {
+    optimize_statement(statement).unwrap_or_else(empty_block)
+}
+
+fn empty_block() -> Statement {
+    Statement::Block(Block {
+        statements: Vec::new(),
+    })
+}
+
+fn take_statement(statement: &mut Statement) -> Statement {
+    std::mem::replace(statement, empty_block())
+}
+
+fn optimize_expression(expression: &mut Expression) {
+    match expression {
+        Expression::Assignment(assignment) => {
+            optimize_assignment_target(&mut assignment.target);
+            optimize_expression(&mut assignment.value);
+        }
+        Expression::Increment(increment) => optimize_assignment_target(&mut increment.target),
+        Expression::Conditional(conditional) => {
+            optimize_expression(&mut conditional.condition);
+            optimize_expression(&mut conditional.then);
+            optimize_expression(&mut conditional.else_);
+
+            match truthiness(&conditional.condition) {
+                Some(true) => *expression = take_expression(&mut conditional.then),
+                Some(false) => *expression = take_expression(&mut conditional.else_),
+                None => (),
+            }
+        }
+        Expression::Call(call) => {
+            optimize_expression(&mut call.callee);
+
+            for argument in &mut call.arguments {
+                optimize_expression(argument);
+            }
+        }
+        Expression::Property(property) => optimize_expression(&mut property.object),
+        Expression::Index(index) => {
+            optimize_expression(&mut index.object);
+            optimize_expression(&mut index.index);
+        }
+        Expression::Unary(unary) => {
+            optimize_expression(&mut unary.right);
+
+            let folded = match (&unary.operator, &*unary.right) {
+                (UnaryOperator::Negate, Expression::Primary(Primary::Number(value))) => {
+                    Some(Primary::Number(-value))
+                }
+                (UnaryOperator::Not, right) => truthiness(right).map(|truthy| boolean(!truthy)),
+                _ => None,
+            };
+
+            if let Some(folded) = folded {
+                *expression = Expression::Primary(folded);
+            }
+        }
+        Expression::Binary(binary) => {
+            optimize_expression(&mut binary.left);
+            optimize_expression(&mut binary.right);
+
+            /* like the evaluator, the logical operators yield one of their operands, and the
+             * right one only matters once the left one is known */
+            match (&binary.operator, truthiness(&binary.left)) {
+                (BinaryOperator::And, Some(true)) | (BinaryOperator::Or, Some(false)) => {
+                    *expression = take_expression(&mut binary.right)
+                }
+                (BinaryOperator::And, Some(false)) | (BinaryOperator::Or, Some(true)) => {
+                    *expression = take_expression(&mut binary.left)
+                }
+                (BinaryOperator::And | BinaryOperator::Or, None) => (),
+                _ => {
+                    if let (Expression::Primary(left), Expression::Primary(right)) =
+                        (&*binary.left, &*binary.right)
+                        && let Some(folded) = fold_binary(&binary.operator, left, right)
+                    {
+                        *expression = Expression::Primary(folded);
+                    }
+                }
+            }
+        }
+        Expression::Primary(primary) => {
+            optimize_primary(primary);
+
+            /* a grouped constant needs no parentheses */
+            if let Primary::Grouping(grouped) = primary
+                && truthiness(grouped).is_some()
+            {
+                *expression = take_expression(grouped);
+            }
+        }
+    }
+}
+
+fn optimize_assignment_target(target: &mut AssignmentTarget) {
+    match target {
+        AssignmentTarget::Identifier(_) => (),
+        AssignmentTarget::Property(property) => optimize_expression(&mut property.object),
+        AssignmentTarget::Index(index) => {
+            optimize_expression(&mut index.object);
+            optimize_expression(&mut index.index);
+        }
+    }
+}
+
+fn optimize_primary(primary: &mut Primary) {
+    match primary {
+        Primary::Grouping(expression) => optimize_expression(expression),
+        Primary::List(elements) => {
+            for element in elements {
+                optimize_expression(element);
+            }
+        }
+        Primary::Map(entries) => {
+            for (key, value) in entries {
+                optimize_expression(key);
+                optimize_expression(value);
+            }
+        }
+        Primary::Function(function) => optimize_function(function),
+        Primary::True | Primary::False | Primary::Nil => (),
+        Primary::Number(_) | Primary::String_(_) | Primary::Identifier(_) => (),
+    }
+}
+
+fn take_expression(expression: &mut Expression) -> Expression {
+    std::mem::replace(expression, Expression::Primary(Primary::Nil))
+}
+
+/* whether an expression is a constant that conditions treat as true, or None if it isn't a
+ * constant. as in the evaluator, only nil and false are falsey */
+fn truthiness(expression: &Expression) -> Option<bool> {
+    match expression {
+        Expression::Primary(Primary::False | Primary::Nil) => Some(false),
+        Expression::Primary(Primary::True | Primary::Number(_) | Primary::String_(_)) => Some(true),
+        _ => None,
+    }
+}
+
+fn boolean(value: bool) -> Primary {
+    match value {
+        true => Primary::True,
+        false => Primary::False,
+    }
+}
+
+/* whether two constants are equal, or None if either isn't a constant. like the evaluator,
+ * constants of different types are never equal */
+fn constants_equal(left: &Primary, right: &Primary) -> Option<bool> {
+    let is_constant = |primary: &Primary| {
+        matches!(
+            primary,
+            Primary::True
+                | Primary::False
+                | Primary::Nil
+                | Primary::Number(_)
+                | Primary::String_(_)
+        )
+    };
+
+    if !is_constant(left) || !is_constant(right) {
+        return None;
+    }
+
+    Some(match (left, right) {
+        (Primary::Number(left), Primary::Number(right)) => left == right,
+        (Primary::String_(left), Primary::String_(right)) => left == right,
+        (Primary::True, Primary::True)
+        | (Primary::False, Primary::False)
+        | (Primary::Nil, Primary::Nil) => true,
+        _ => false,
+    })
+}
+
+/* the result of an operator on two constants, computed the way the evaluator would. None when
+ * it would fail instead, so that the error is still raised when the program runs */
+fn fold_binary(operator: &BinaryOperator, left: &Primary, right: &Primary) -> Option<Primary> {
+    if let Some(equal) = constants_equal(left, right) {
+        match operator {
+            BinaryOperator::Equality => return Some(boolean(equal)),
+            BinaryOperator::Inequality => return Some(boolean(!equal)),
+            _ => (),
+        }
+    }
+
+    if let (Primary::String_(left), Primary::String_(right)) = (left, right) {
+        return match operator {
+            BinaryOperator::Addition => {
+                Some(Primary::String_(Rc::from([&**left, &**right].concat())))
+            }
+            _ => None,
+        };
+    }
+
+    let (&Primary::Number(left), &Primary::Number(right)) = (left, right) else {
+        return None;
+    };
+
+    let result = match operator {
+        BinaryOperator::GreaterThan => return Some(boolean(left > right)),
+        BinaryOperator::GreaterThanOrEqualTo => return Some(boolean(left >= right)),
+        BinaryOperator::LessThan => return Some(boolean(left < right)),
+        BinaryOperator::LessThanOrEqualTo => return Some(boolean(left <= right)),
+        BinaryOperator::Addition => left + right,
+        BinaryOperator::Subtraction => left - right,
+        BinaryOperator::Multiplication => left * right,
+        BinaryOperator::Division => left / right,
+        BinaryOperator::IntegerDivision | BinaryOperator::Modulo if right == 0.0 => return None,
+        BinaryOperator::IntegerDivision => (left / right).floor(),
+        BinaryOperator::Modulo => {
+            let remainder = left % right;
+
+            match remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+                true => remainder + right,
+                false => remainder,
+            }
+        }
+        BinaryOperator::Exponentiation => left.powf(right),
+        BinaryOperator::Equality
+        | BinaryOperator::Inequality
+        | BinaryOperator::And
+        | BinaryOperator::Or => return None,
+    };
+
+    Some(Primary::Number(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn optimize(code: &str) -> String {
+        let tokens = lexer::tokenize(code).unwrap();
+        let mut ast = Ast::new(tokens.into_iter()).unwrap();
+        ast.resolve().unwrap();
+        ast.optimize();
+
+        ast.to_string().trim_end().to_string()
+    }
+
+    #[test]
+    fn test_fold_arithmetic() {
+        assert_eq!(optimize("print 2 * 3 + 1;"), optimize("print 7;"));
+        assert_eq!(optimize("print -(2 ** 3) % 5;"), optimize("print 2;"));
+        assert_eq!(optimize("print 7 // 2 + x;"), optimize("print 3 + x;"));
+        assert_eq!(optimize("print 1 < 2;"), optimize("print true;"));
+    }
+
+    #[test]
+    fn test_fold_strings() {
+        assert_eq!(optimize("print \"a\" + \"b\";"), optimize("print \"ab\";"));
+    }
+
+    #[test]
+    fn test_fold_logic() {
+        assert_eq!(optimize("print !nil and x;"), optimize("print x;"));
+        assert_eq!(optimize("print 0 or x;"), optimize("print 0;"));
+        assert_eq!(optimize("print true == !1;"), optimize("print false;"));
+        assert_eq!(optimize("print false ? x : 1 + 1;"), optimize("print 2;"));
+        assert_eq!(optimize("print x and true;"), "(print (and x true))");
+    }
+
+    #[test]
+    fn test_fold_equality() {
+        assert_eq!(optimize("print \"a\" == \"a\";"), optimize("print true;"));
+        assert_eq!(optimize("print nil != false;"), optimize("print true;"));
+        assert_eq!(optimize("print 1 == \"1\";"), optimize("print false;"));
+        assert_eq!(optimize("print x == 1;"), "(print (== x 1.0))");
+    }
+
+    #[test]
+    fn test_failing_operations_are_kept() {
+        assert_eq!(optimize("print 1 // 0;"), "(print (// 1.0 0.0))");
+        assert_eq!(optimize("print 1 % (0 * 2);"), "(print (% 1.0 0.0))");
+        assert_eq!(optimize("print \"a\" + 1;"), "(print (+ \"a\" 1.0))");
+    }
+
+    #[test]
+    fn test_dead_branches() {
+        assert_eq!(optimize("if (false) print 1;"), "");
+        assert_eq!(optimize("if (1 > 2) print 1; else print 2;"), "(print 2.0)");
+        assert_eq!(optimize("if (nil) print 1; print 3;"), "(print 3.0)");
+        assert_eq!(
+            optimize("while (x) if (false) print 1;"),
+            optimize("while (x) {}")
+        );
+    }
+
+    #[test]
+    fn test_unreachable_after_return() {
+        assert_eq!(
+            optimize("fun f() { print 1; return 2; print 3; var a; }"),
+            optimize("fun f() { print 1; return 2; }")
+        );
+        assert_eq!(
+            optimize("fun f() { if (true) return 1; print 2; }"),
+            optimize("fun f() { return 1; }")
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use lexer::Token;
 
+use std::collections::HashSet;
+use std::rc::Rc;
+
 #[derive(Debug, Clone)]
 pub enum ParseErrorKind {
     UnexpectedToken,
@@ -8,10 +11,20 @@ pub enum ParseErrorKind {
     ExpectedEndOfExpression,
     ExpectedSemicolon,
     ExpectedIdentifier,
+    TooManyArguments,
+    TooManyParameters,
+    InvalidAssignmentTarget,
 }
 
+/// The maximum number of arguments a call expression may pass, and so the maximum number of
+/// parameters a function may declare.
+pub const MAX_ARGUMENTS: usize = 255;
+
+#[derive(Debug)]
 pub struct Ast {
     pub program: crate::grammar::Program,
+    /* set once `resolve` has bound every variable without errors */
+    pub(crate) resolved: bool,
 }
 
 impl Ast {
@@ -21,7 +34,10 @@ impl Ast {
         let mut parse_context = ParseContext::<_>::new(tokens);
 
         match crate::grammar::Program::parse(&mut parse_context) {
-            Ok(program) => Ok(Ast { program }),
+            Ok(program) => Ok(Ast {
+                program,
+                resolved: false,
+            }),
             Err(_) => Err(parse_context.errors()),
         }
     }
@@ -39,13 +55,13 @@ pub enum ShouldSynchronize {
 }
 
 pub type ParseResult<T> = Result<T, ShouldSynchronize>;
-pub type ParseError = error::RecordedError<ParseErrorKind>;
 
 pub struct ParseContext<I>
 where
     I: Iterator<Item = Token>,
 {
     recorder: error::ErrorRecorder<ParseErrorKind, I>,
+    strings: HashSet<Rc<str>>,
 }
 
 impl ParseContext<error::DummyIterator> {
@@ -54,6 +70,7 @@ impl ParseContext<error::DummyIterator> {
     ) -> ParseContext<impl Iterator<Item = Token>> {
         ParseContext {
             recorder: error::ErrorRecorder::new(tokens),
+            strings: HashSet::new(),
         }
     }
 }
@@ -80,6 +97,22 @@ where
         }
     }
 
+    /// The line, counting from 1, that the next token is on.
+    pub fn line(&mut self) -> usize {
+        self.recorder.line()
+    }
+
+    /// The storage for a string constant, shared with every equal constant parsed before it.
+    pub fn string(&mut self, value: Rc<str>) -> Rc<str> {
+        match self.strings.get(&value) {
+            Some(shared) => shared.clone(),
+            None => {
+                self.strings.insert(value.clone());
+                value
+            }
+        }
+    }
+
     pub fn record_error(&mut self, kind: ParseErrorKind) {
         self.recorder.record(kind)
     }
@@ -88,13 +121,3 @@ where
         self.recorder.errors()
     }
 }
-
-fn synchronize_default(tokens: &mut impl Iterator<Item = Token>) {
-    synchronize(tokens, lexer::tokens::FixedToken::Semicolon)
-}
-
-fn synchronize(tokens: &mut impl Iterator<Item = Token>, token: lexer::tokens::FixedToken) {
-    tokens.find(|t| {
-        matches!(t, Token::FixedToken(fixed_token) if std::mem::discriminant(fixed_token) == std::mem::discriminant(&token))
-    });
-}
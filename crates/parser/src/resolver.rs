@@ -0,0 +1,405 @@
+use crate::Ast;
+use crate::grammar::*;
+
+use lexer::Symbol;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub enum ResolveError {
+    ReadInOwnInitializer(Symbol),
+    DuplicateDeclaration(Symbol),
+    ReturnOutsideFunction,
+}
+
+impl Ast {
+    /// Binds every variable use to its declaration, filling in each `Variable::local`. This must
+    /// run before the tree is evaluated, which relies on it to find local variables.
+    pub fn resolve(&mut self) -> Result<(), Vec<ResolveError>> {
+        let mut resolver = Resolver::new();
+        resolver.program(&mut self.program);
+
+        self.resolved = resolver.errors.is_empty();
+
+        match self.resolved {
+            true => Ok(()),
+            false => Err(resolver.errors),
+        }
+    }
+
+    /// Whether `resolve` has run on the tree and succeeded.
+    pub fn is_resolved(&self) -> bool {
+        self.resolved
+    }
+}
+
+struct Resolver {
+    /* local scopes, innermost last */
+    scopes: Vec<HashMap<Symbol, Binding>>,
+    function_depth: usize,
+    errors: Vec<ResolveError>,
+}
+
+struct Binding {
+    slot: usize,
+    /* false while the variable's own initializer is being resolved */
+    defined: bool,
+}
+
+impl Resolver {
+    fn new() -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            function_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    fn program(&mut self, program: &mut Program) {
+        for declaration in &mut program.declarations {
+            self.declaration(declaration);
+        }
+    }
+
+    fn declaration(&mut self, declaration: &mut Declaration) {
+        match &mut declaration.kind {
+            DeclarationKind::VariableDeclaration(variable_declaration) => {
+                self.variable_declaration(variable_declaration)
+            }
+            DeclarationKind::FunctionDeclaration(function) => {
+                /* declared before the body is resolved, so that the function can call itself */
+                if let Some(name) = function.name {
+                    self.declare(name);
+                    self.define(name);
+                }
+
+                self.function(function);
+            }
+            DeclarationKind::Statement(statement) => self.statement(statement),
+        }
+    }
+
+    fn variable_declaration(&mut self, variable_declaration: &mut VariableDeclaration) {
+        self.declare(variable_declaration.identifier);
+
+        if let Some(value) = &mut variable_declaration.value {
+            self.expression(value);
+        }
+
+        self.define(variable_declaration.identifier);
+    }
+
+    fn function(&mut self, function: &mut Rc<Function>) {
+        let function =
+            Rc::get_mut(function).expect("functions are resolved before they are shared");
+
+        self.function_depth += 1;
+        self.scopes.push(HashMap::new());
+
+        for &parameter in &function.parameters {
+            self.declare(parameter);
+            self.define(parameter);
+        }
+
+        for declaration in &mut function.body {
+            self.declaration(declaration);
+        }
+
+        self.scopes.pop();
+        self.function_depth -= 1;
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::ExpressionStatement(expression) | Statement::PrintStatement(expression) => {
+                self.expression(expression)
+            }
+            Statement::ForStatement {
+                initializer,
+                condition,
+                expression,
+                body,
+            } => {
+                self.scopes.push(HashMap::new());
+
+                match initializer {
+                    ForLoopInitializer::Declaration(variable_declaration) => {
+                        self.variable_declaration(variable_declaration)
+                    }
+                }
+
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+
+                if let Some(expression) = expression {
+                    self.expression(expression);
+                }
+
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Statement::IfStatement {
+                condition,
+                then,
+                else_,
+            } => {
+                self.expression(condition);
+                self.statement(then);
+
+                if let Some(else_) = else_ {
+                    self.statement(else_);
+                }
+            }
+            Statement::ReturnStatement(value) => {
+                if self.function_depth == 0 {
+                    self.errors.push(ResolveError::ReturnOutsideFunction);
+                }
+
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::WhileStatement { condition, body } => {
+                self.expression(condition);
+                self.statement(body);
+            }
+            Statement::Block(block) => {
+                self.scopes.push(HashMap::new());
+
+                for declaration in &mut block.statements {
+                    self.declaration(declaration);
+                }
+
+                self.scopes.pop();
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::Assignment(assignment) => {
+                self.expression(&mut assignment.value);
+                self.assignment_target(&mut assignment.target);
+            }
+            Expression::Increment(increment) => self.assignment_target(&mut increment.target),
+            Expression::Conditional(conditional) => {
+                self.expression(&mut conditional.condition);
+                self.expression(&mut conditional.then);
+                self.expression(&mut conditional.else_);
+            }
+            Expression::Call(call) => {
+                self.expression(&mut call.callee);
+
+                for argument in &mut call.arguments {
+                    self.expression(argument);
+                }
+            }
+            Expression::Property(property) => self.expression(&mut property.object),
+            Expression::Index(index) => {
+                self.expression(&mut index.object);
+                self.expression(&mut index.index);
+            }
+            Expression::Unary(unary) => self.expression(&mut unary.right),
+            Expression::Binary(binary) => {
+                self.expression(&mut binary.left);
+                self.expression(&mut binary.right);
+            }
+            Expression::Primary(primary) => self.primary(primary),
+        }
+    }
+
+    fn assignment_target(&mut self, target: &mut AssignmentTarget) {
+        match target {
+            AssignmentTarget::Identifier(variable) => self.variable(variable),
+            AssignmentTarget::Property(property) => self.expression(&mut property.object),
+            AssignmentTarget::Index(index) => {
+                self.expression(&mut index.object);
+                self.expression(&mut index.index);
+            }
+        }
+    }
+
+    fn primary(&mut self, primary: &mut Primary) {
+        match primary {
+            Primary::Identifier(variable) => {
+                if let Some(Binding { defined: false, .. }) = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&variable.name))
+                {
+                    self.errors
+                        .push(ResolveError::ReadInOwnInitializer(variable.name));
+                }
+
+                self.variable(variable);
+            }
+            Primary::Grouping(expression) => self.expression(expression),
+            Primary::List(elements) => {
+                for element in elements {
+                    self.expression(element);
+                }
+            }
+            Primary::Map(entries) => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Primary::Function(function) => self.function(function),
+            Primary::True | Primary::False | Primary::Nil => (),
+            Primary::Number(_) | Primary::String_(_) => (),
+        }
+    }
+
+    fn variable(&mut self, variable: &mut Variable) {
+        variable.local = self
+            .scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| {
+                let slot = scope.get(&variable.name)?.slot;
+                Some(Local { depth, slot })
+            });
+    }
+
+    /* globals may be redeclared, so only local scopes are tracked. slots are handed out in
+     * declaration order, which is the order the evaluator pushes locals onto a frame */
+    fn declare(&mut self, name: Symbol) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        if scope.contains_key(&name) {
+            self.errors.push(ResolveError::DuplicateDeclaration(name));
+            return;
+        }
+
+        let slot = scope.len();
+        scope.insert(
+            name,
+            Binding {
+                slot,
+                defined: false,
+            },
+        );
+    }
+
+    fn define(&mut self, name: Symbol) {
+        if let Some(binding) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name))
+        {
+            binding.defined = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(code: &str) -> Result<Ast, Vec<ResolveError>> {
+        let tokens = lexer::tokenize(code).unwrap();
+        let mut ast = Ast::new(tokens.into_iter()).unwrap();
+
+        ast.resolve().map(|()| ast)
+    }
+
+    /* the depth and slot of the variable printed by the program's last statement */
+    fn local(code: &str) -> Option<(usize, usize)> {
+        let ast = resolve(code).unwrap();
+
+        let mut declaration = ast.program.declarations.last().unwrap();
+
+        loop {
+            match &declaration.kind {
+                DeclarationKind::Statement(Statement::Block(block)) => {
+                    declaration = block.statements.last().unwrap()
+                }
+                DeclarationKind::Statement(Statement::PrintStatement(Expression::Primary(
+                    Primary::Identifier(variable),
+                ))) => return variable.local.map(|local| (local.depth, local.slot)),
+                _ => panic!("expected a print statement in {code}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_resolved() {
+        let tokens = lexer::tokenize("{ var a = 1; print a; }").unwrap();
+        let mut ast = Ast::new(tokens.into_iter()).unwrap();
+        assert!(!ast.is_resolved());
+
+        ast.resolve().unwrap();
+        assert!(ast.is_resolved());
+
+        let tokens = lexer::tokenize("{ var a = a; }").unwrap();
+        let mut ast = Ast::new(tokens.into_iter()).unwrap();
+        assert!(ast.resolve().is_err());
+        assert!(!ast.is_resolved());
+    }
+
+    #[test]
+    fn test_local() {
+        assert_eq!(local("var a; print a;"), None);
+        assert_eq!(local("{ var a; print a; }"), Some((0, 0)));
+        assert_eq!(local("{ var a; var b; { { print b; } } }"), Some((2, 1)));
+        assert_eq!(local("{ var a; { var b; var a; print a; } }"), Some((0, 1)));
+        assert_eq!(local("{ { print a; } }"), None);
+    }
+
+    #[test]
+    fn test_function_slots() {
+        let ast = resolve("fun f(a, b) { fun g() {} var c; print c; }").unwrap();
+
+        let DeclarationKind::FunctionDeclaration(function) = &ast.program.declarations[0].kind
+        else {
+            panic!("expected a function declaration");
+        };
+        let Some(DeclarationKind::Statement(Statement::PrintStatement(Expression::Primary(
+            Primary::Identifier(variable),
+        )))) = function.body.last().map(|declaration| &declaration.kind)
+        else {
+            panic!("expected a print statement");
+        };
+
+        assert!(matches!(variable.local, Some(Local { depth: 0, slot: 3 })));
+    }
+
+    #[test]
+    fn test_read_in_own_initializer() {
+        assert!(matches!(
+            resolve("{ var a = a; }").unwrap_err()[..],
+            [ResolveError::ReadInOwnInitializer(_)]
+        ));
+        assert!(resolve("var a = 1; { var a = a + 1; }").is_err());
+        assert!(resolve("var a = a;").is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_declaration() {
+        assert!(matches!(
+            resolve("{ var a; var a; }").unwrap_err()[..],
+            [ResolveError::DuplicateDeclaration(_)]
+        ));
+        assert!(resolve("fun f(a, a) {}").is_err());
+        assert!(resolve("fun f(a) { var a; }").is_err());
+        assert!(resolve("{ var a; { var a; } }").is_ok());
+        assert!(resolve("var a; var a;").is_ok());
+    }
+
+    #[test]
+    fn test_return_outside_function() {
+        assert!(matches!(
+            resolve("return 1;").unwrap_err()[..],
+            [ResolveError::ReturnOutsideFunction]
+        ));
+        assert!(resolve("{ return; }").is_err());
+        assert!(resolve("fun f() { { return; } }").is_ok());
+        assert!(resolve("var f = () => { return 1; };").is_ok());
+    }
+}
@@ -0,0 +1,55 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+
+use evaluator::Evaluator;
+use vm::Vm;
+
+fn parse(code: &str) -> parser::Ast {
+    let tokens = lexer::tokenize(code).unwrap();
+    let mut ast = parser::Ast::new(tokens.into_iter()).unwrap();
+    ast.resolve().unwrap();
+
+    ast
+}
+
+/* runs the same program on both engines so their timings can be compared */
+fn run(criterion: &mut Criterion, name: &str, code: &str) {
+    let ast = parse(code);
+    let prototype = vm::compile(&ast).unwrap();
+
+    let mut group = criterion.benchmark_group(name);
+
+    group.bench_function("evaluator", |bencher| {
+        bencher.iter(|| Evaluator::new().evaluate(&ast).unwrap())
+    });
+    group.bench_function("vm", |bencher| {
+        bencher.iter(|| Vm::new().run(prototype.clone()).unwrap())
+    });
+
+    group.finish();
+}
+
+fn fib(criterion: &mut Criterion) {
+    run(
+        criterion,
+        "fib",
+        "fun fib(n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); } fib(20);",
+    );
+}
+
+fn nested_loops(criterion: &mut Criterion) {
+    run(
+        criterion,
+        "nested loops",
+        "{
+            var sum = 0;
+            for (var i = 0; i < 100; i = i + 1) {
+                for (var j = 0; j < 100; j = j + 1) {
+                    sum = sum + i * j;
+                }
+            }
+        }",
+    );
+}
+
+criterion_group!(benches, fib, nested_loops);
+criterion_main!(benches);
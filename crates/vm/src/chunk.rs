@@ -0,0 +1,227 @@
+use std::rc::Rc;
+
+/// The instructions of the virtual machine. Each opcode is one byte, followed by its operands:
+/// one byte for local slots, upvalues and argument counts and two big-endian bytes for constant
+/// indices, jump offsets and collection sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OpCode {
+    /* constant index */
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    Dup,
+    /* duplicates the top two values, keeping their order */
+    Dup2,
+    /* moves the top value below the `n` values under it */
+    Insert,
+    /* local slot */
+    GetLocal,
+    SetLocal,
+    /* upvalue index */
+    GetUpvalue,
+    SetUpvalue,
+    /* constant index of the name */
+    DefineGlobal,
+    GetGlobal,
+    SetGlobal,
+    GetIndex,
+    SetIndex,
+    /* constant index of the name. no value has properties yet, so this always fails */
+    GetProperty,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    IntegerDivide,
+    Modulo,
+    Power,
+    Not,
+    Negate,
+    Print,
+    /* forward offset */
+    Jump,
+    JumpIfFalse,
+    /* backward offset */
+    Loop,
+    /* argument count */
+    Call,
+    /* constant index of the function */
+    Closure,
+    CloseUpvalue,
+    Return,
+    /* element count */
+    List,
+    /* entry count */
+    Map,
+}
+
+impl OpCode {
+    /// The number of operand bytes that follow the opcode.
+    pub fn operand_size(self) -> usize {
+        match self {
+            OpCode::Insert
+            | OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::Call => 1,
+            OpCode::Constant
+            | OpCode::DefineGlobal
+            | OpCode::GetGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::Loop
+            | OpCode::Closure
+            | OpCode::List
+            | OpCode::Map => 2,
+            _ => 0,
+        }
+    }
+}
+
+/* every opcode, in the order of their numbering */
+const OPCODES: [OpCode; 43] = [
+    OpCode::Constant,
+    OpCode::Nil,
+    OpCode::True,
+    OpCode::False,
+    OpCode::Pop,
+    OpCode::Dup,
+    OpCode::Dup2,
+    OpCode::Insert,
+    OpCode::GetLocal,
+    OpCode::SetLocal,
+    OpCode::GetUpvalue,
+    OpCode::SetUpvalue,
+    OpCode::DefineGlobal,
+    OpCode::GetGlobal,
+    OpCode::SetGlobal,
+    OpCode::GetIndex,
+    OpCode::SetIndex,
+    OpCode::GetProperty,
+    OpCode::Equal,
+    OpCode::NotEqual,
+    OpCode::Greater,
+    OpCode::GreaterEqual,
+    OpCode::Less,
+    OpCode::LessEqual,
+    OpCode::Add,
+    OpCode::Subtract,
+    OpCode::Multiply,
+    OpCode::Divide,
+    OpCode::IntegerDivide,
+    OpCode::Modulo,
+    OpCode::Power,
+    OpCode::Not,
+    OpCode::Negate,
+    OpCode::Print,
+    OpCode::Jump,
+    OpCode::JumpIfFalse,
+    OpCode::Loop,
+    OpCode::Call,
+    OpCode::Closure,
+    OpCode::CloseUpvalue,
+    OpCode::Return,
+    OpCode::List,
+    OpCode::Map,
+];
+
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OPCODES.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+/// A value known at compile time, which instructions refer to by its index in the pool.
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Number(f64),
+    String_(Rc<str>),
+    Function(Rc<Prototype>),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    /* the source line of each run of bytes, as the offset the run starts at and its line */
+    pub lines: Vec<(usize, usize)>,
+}
+
+impl Chunk {
+    /// Appends bytes compiled from the given source line.
+    pub fn write(&mut self, bytes: &[u8], line: usize) {
+        if self.lines.last().is_none_or(|&(_, last)| last != line) {
+            self.lines.push((self.code.len(), line));
+        }
+
+        self.code.extend_from_slice(bytes);
+    }
+
+    /// The source line the byte at `offset` was compiled from.
+    pub fn line(&self, offset: usize) -> usize {
+        let run = self.lines.partition_point(|&(start, _)| start <= offset);
+
+        match run {
+            0 => 0,
+            run => self.lines[run - 1].1,
+        }
+    }
+
+    pub fn read_u8(&self, offset: usize) -> u8 {
+        self.code[offset]
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+}
+
+/// A compiled function, from which the machine creates closures at run time. The top level of
+/// a program is compiled to a prototype without a name or parameters.
+#[derive(Debug, Clone)]
+pub struct Prototype {
+    pub name: Option<String>,
+    pub arity: usize,
+    pub upvalues: Vec<UpvalueSource>,
+    pub chunk: Chunk,
+}
+
+/// Where a closure finds one of its upvalues when it is created: in a local slot of the
+/// enclosing function's frame, or among the enclosing closure's own upvalues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpvalueSource {
+    Local(u8),
+    Upvalue(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_numbering() {
+        for (byte, opcode) in OPCODES.iter().enumerate() {
+            assert_eq!(*opcode as u8 as usize, byte);
+            assert_eq!(OpCode::try_from(byte as u8), Ok(*opcode));
+        }
+
+        assert_eq!(
+            OpCode::try_from(OPCODES.len() as u8),
+            Err(OPCODES.len() as u8)
+        );
+    }
+}
@@ -0,0 +1,799 @@
+use crate::chunk::{Chunk, Constant, OpCode, Prototype, UpvalueSource};
+
+use parser::Ast;
+use parser::grammar::*;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub enum CompileError {
+    TooManyConstants,
+    TooManyLocals,
+    TooManyUpvalues,
+    TooManyElements,
+    JumpTooLarge,
+    /* the ast was compiled without being resolved, so its locals can't be found */
+    UnresolvedAst,
+}
+
+type CompileResult<T> = Result<T, CompileError>;
+
+/// Compiles a resolved program to the prototype of its top level, which the machine runs as a
+/// function without parameters.
+pub fn compile(ast: &Ast) -> CompileResult<Rc<Prototype>> {
+    if !ast.is_resolved() {
+        return Err(CompileError::UnresolvedAst);
+    }
+
+    let mut compiler = Compiler::new();
+
+    for declaration in &ast.program.declarations {
+        compiler.declaration(declaration)?;
+    }
+
+    /* the implicit return belongs to the program's last declaration */
+    compiler.line = ast.program.declarations.last().map_or(1, |last| last.line);
+    compiler.emit(OpCode::Nil);
+    compiler.emit(OpCode::Return);
+
+    Ok(Rc::new(compiler.functions.pop().unwrap().prototype))
+}
+
+struct Compiler {
+    /* the functions being compiled, innermost last. the first is the top level */
+    functions: Vec<FunctionState>,
+    /* the local scopes, innermost last, mirroring the ones the resolver counts depths in */
+    scopes: Vec<ScopeState>,
+    /* the line of the declaration being compiled, which emitted instructions are tagged with */
+    line: usize,
+}
+
+struct FunctionState {
+    prototype: Prototype,
+    /* the number of local slots in use, which is also the next free one */
+    locals: usize,
+    /* for each slot in use, whether a closure captured it */
+    captured: Vec<bool>,
+    /* the constant holding each string, so that names used repeatedly are stored once */
+    strings: HashMap<Rc<str>, u16>,
+}
+
+struct ScopeState {
+    function: usize,
+    /* the slot of the scope's first local, which the resolver numbers 0 */
+    base: usize,
+}
+
+impl FunctionState {
+    fn new(name: Option<String>, arity: usize) -> Self {
+        FunctionState {
+            prototype: Prototype {
+                name,
+                arity,
+                upvalues: Vec::new(),
+                chunk: Chunk::default(),
+            },
+            locals: 0,
+            captured: Vec::new(),
+            strings: HashMap::new(),
+        }
+    }
+}
+
+impl Compiler {
+    fn new() -> Self {
+        Compiler {
+            functions: vec![FunctionState::new(None, 0)],
+            scopes: Vec::new(),
+            line: 0,
+        }
+    }
+
+    fn function_state(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.function_state().prototype.chunk
+    }
+
+    fn emit(&mut self, opcode: OpCode) {
+        let line = self.line;
+        self.chunk().write(&[opcode as u8], line);
+    }
+
+    fn emit_u8(&mut self, opcode: OpCode, operand: u8) {
+        let line = self.line;
+        self.chunk().write(&[opcode as u8, operand], line);
+    }
+
+    fn emit_u16(&mut self, opcode: OpCode, operand: u16) {
+        let line = self.line;
+        let [high, low] = operand.to_be_bytes();
+        self.chunk().write(&[opcode as u8, high, low], line);
+    }
+
+    fn constant(&mut self, constant: Constant) -> CompileResult<u16> {
+        let constants = &mut self.chunk().constants;
+        let index = u16::try_from(constants.len()).map_err(|_| CompileError::TooManyConstants)?;
+
+        constants.push(constant);
+        Ok(index)
+    }
+
+    fn string(&mut self, string: &str) -> CompileResult<u16> {
+        if let Some(&index) = self.function_state().strings.get(string) {
+            return Ok(index);
+        }
+
+        let string = Rc::<str>::from(string);
+        let index = self.constant(Constant::String_(string.clone()))?;
+        self.function_state().strings.insert(string, index);
+
+        Ok(index)
+    }
+
+    /* emits a jump to be patched once its target is known, returning where its offset goes */
+    fn emit_jump(&mut self, opcode: OpCode) -> usize {
+        self.emit_u16(opcode, u16::MAX);
+        self.chunk().code.len() - 2
+    }
+
+    /* points the jump whose offset is at `operand` to the next instruction */
+    fn patch_jump(&mut self, operand: usize) -> CompileResult<()> {
+        let code = &mut self.chunk().code;
+        let offset =
+            u16::try_from(code.len() - operand - 2).map_err(|_| CompileError::JumpTooLarge)?;
+
+        code[operand..operand + 2].copy_from_slice(&offset.to_be_bytes());
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize) -> CompileResult<()> {
+        /* the offset is taken from the end of the loop instruction */
+        let offset = self.chunk().code.len() + 3 - start;
+        let offset = u16::try_from(offset).map_err(|_| CompileError::JumpTooLarge)?;
+
+        self.emit_u16(OpCode::Loop, offset);
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        let function = self.functions.len() - 1;
+        let base = self.function_state().locals;
+
+        self.scopes.push(ScopeState { function, base });
+    }
+
+    /* pops the scope's locals, moving those that closures captured off the stack */
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+
+        for slot in (scope.base..self.function_state().locals).rev() {
+            match self.function_state().captured[slot] {
+                true => self.emit(OpCode::CloseUpvalue),
+                false => self.emit(OpCode::Pop),
+            }
+        }
+
+        let function = self.function_state();
+        function.locals = scope.base;
+        function.captured.truncate(scope.base);
+    }
+
+    /* claims the next slot for a local, whose value is the one on top of the stack */
+    fn declare_local(&mut self) -> CompileResult<()> {
+        let function = self.function_state();
+
+        if function.locals > u8::MAX as usize {
+            return Err(CompileError::TooManyLocals);
+        }
+
+        function.locals += 1;
+        function.captured.push(false);
+        Ok(())
+    }
+
+    fn declaration(&mut self, declaration: &Declaration) -> CompileResult<()> {
+        /* restored afterwards, so that a function's closure is tagged with its declaration's line
+         * rather than the last line of its body */
+        let line = std::mem::replace(&mut self.line, declaration.line);
+        let result = self.declaration_kind(&declaration.kind);
+        self.line = line;
+
+        result
+    }
+
+    fn declaration_kind(&mut self, declaration: &DeclarationKind) -> CompileResult<()> {
+        match declaration {
+            DeclarationKind::VariableDeclaration(variable_declaration) => {
+                self.variable_declaration(variable_declaration)
+            }
+            DeclarationKind::FunctionDeclaration(function) => {
+                let name = function.name.expect("declared functions are named");
+
+                match self.scopes.is_empty() {
+                    true => {
+                        self.function(function)?;
+                        let name = self.string(name.as_str())?;
+                        self.emit_u16(OpCode::DefineGlobal, name);
+                    }
+                    /* the slot is claimed first, so that the function can capture itself */
+                    false => {
+                        self.declare_local()?;
+                        self.function(function)?;
+                    }
+                }
+
+                Ok(())
+            }
+            DeclarationKind::Statement(statement) => self.statement(statement),
+        }
+    }
+
+    fn variable_declaration(&mut self, declaration: &VariableDeclaration) -> CompileResult<()> {
+        match &declaration.value {
+            Some(value) => self.expression(value)?,
+            None => self.emit(OpCode::Nil),
+        }
+
+        match self.scopes.is_empty() {
+            true => {
+                let name = self.string(declaration.identifier.as_str())?;
+                self.emit_u16(OpCode::DefineGlobal, name);
+                Ok(())
+            }
+            false => self.declare_local(),
+        }
+    }
+
+    /* compiles a function and emits the instruction that creates a closure of it */
+    fn function(&mut self, function: &Function) -> CompileResult<()> {
+        self.functions.push(FunctionState::new(
+            function.name.map(|name| String::from(name.as_str())),
+            function.parameters.len(),
+        ));
+        self.begin_scope();
+
+        for _ in &function.parameters {
+            self.declare_local()?;
+        }
+
+        for declaration in &function.body {
+            self.declaration(declaration)?;
+        }
+
+        self.emit(OpCode::Nil);
+        self.emit(OpCode::Return);
+
+        /* returning pops the frame, so the scope's locals need no instructions of their own */
+        self.scopes.pop();
+        let prototype = self.functions.pop().unwrap().prototype;
+        let constant = self.constant(Constant::Function(Rc::new(prototype)))?;
+        self.emit_u16(OpCode::Closure, constant);
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> CompileResult<()> {
+        match statement {
+            Statement::ExpressionStatement(expression) => {
+                self.expression(expression)?;
+                self.emit(OpCode::Pop);
+            }
+            Statement::ForStatement {
+                initializer,
+                condition,
+                expression,
+                body,
+            } => {
+                self.begin_scope();
+
+                match initializer {
+                    ForLoopInitializer::Declaration(declaration) => {
+                        self.variable_declaration(declaration)?
+                    }
+                }
+
+                let start = self.chunk().code.len();
+
+                let exit = match condition {
+                    Some(condition) => {
+                        self.expression(condition)?;
+                        let exit = self.emit_jump(OpCode::JumpIfFalse);
+                        self.emit(OpCode::Pop);
+                        Some(exit)
+                    }
+                    None => None,
+                };
+
+                self.statement(body)?;
+
+                if let Some(expression) = expression {
+                    self.expression(expression)?;
+                    self.emit(OpCode::Pop);
+                }
+
+                self.emit_loop(start)?;
+
+                if let Some(exit) = exit {
+                    self.patch_jump(exit)?;
+                    self.emit(OpCode::Pop);
+                }
+
+                self.end_scope();
+            }
+            Statement::IfStatement {
+                condition,
+                then,
+                else_,
+            } => {
+                self.expression(condition)?;
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.statement(then)?;
+
+                let end_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump)?;
+                self.emit(OpCode::Pop);
+
+                if let Some(else_) = else_ {
+                    self.statement(else_)?;
+                }
+
+                self.patch_jump(end_jump)?;
+            }
+            Statement::PrintStatement(expression) => {
+                self.expression(expression)?;
+                self.emit(OpCode::Print);
+            }
+            Statement::ReturnStatement(value) => {
+                match value {
+                    Some(value) => self.expression(value)?,
+                    None => self.emit(OpCode::Nil),
+                }
+
+                self.emit(OpCode::Return);
+            }
+            Statement::WhileStatement { condition, body } => {
+                let start = self.chunk().code.len();
+
+                self.expression(condition)?;
+                let exit = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.statement(body)?;
+                self.emit_loop(start)?;
+
+                self.patch_jump(exit)?;
+                self.emit(OpCode::Pop);
+            }
+            Statement::Block(block) => {
+                self.begin_scope();
+
+                for declaration in &block.statements {
+                    self.declaration(declaration)?;
+                }
+
+                self.end_scope();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &Expression) -> CompileResult<()> {
+        match expression {
+            Expression::Assignment(assignment) => self.assignment(assignment),
+            Expression::Increment(increment) => self.increment(increment),
+            Expression::Conditional(conditional) => {
+                self.expression(&conditional.condition)?;
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.expression(&conditional.then)?;
+
+                let end_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump)?;
+                self.emit(OpCode::Pop);
+                self.expression(&conditional.else_)?;
+
+                self.patch_jump(end_jump)
+            }
+            Expression::Call(call) => {
+                self.expression(&call.callee)?;
+
+                for argument in &call.arguments {
+                    self.expression(argument)?;
+                }
+
+                /* the parser allows no more arguments than fit in the operand */
+                self.emit_u8(OpCode::Call, call.arguments.len() as u8);
+                Ok(())
+            }
+            Expression::Property(property) => self.property(property),
+            Expression::Index(index) => {
+                self.expression(&index.object)?;
+                self.expression(&index.index)?;
+                self.emit(OpCode::GetIndex);
+                Ok(())
+            }
+            Expression::Unary(unary) => {
+                self.expression(&unary.right)?;
+
+                match unary.operator {
+                    UnaryOperator::Negate => self.emit(OpCode::Negate),
+                    UnaryOperator::Not => self.emit(OpCode::Not),
+                }
+
+                Ok(())
+            }
+            Expression::Binary(binary) => self.binary(binary),
+            Expression::Primary(primary) => self.primary(primary),
+        }
+    }
+
+    /* no value has properties, so the object is evaluated for its side effects before failing */
+    fn property(&mut self, property: &Property) -> CompileResult<()> {
+        self.expression(&property.object)?;
+
+        let name = self.string(property.name.as_str())?;
+        self.emit_u16(OpCode::GetProperty, name);
+        Ok(())
+    }
+
+    /* leaves the assigned value on the stack, as the value of the assignment expression */
+    fn assignment(&mut self, assignment: &Assignment) -> CompileResult<()> {
+        match &assignment.target {
+            AssignmentTarget::Identifier(variable) => {
+                if let Some(operator) = &assignment.operator {
+                    self.get_variable(variable)?;
+                    self.expression(&assignment.value)?;
+                    self.binary_operator(operator);
+                } else {
+                    self.expression(&assignment.value)?;
+                }
+
+                self.set_variable(variable)
+            }
+            AssignmentTarget::Index(index) => {
+                self.expression(&index.object)?;
+                self.expression(&index.index)?;
+
+                if let Some(operator) = &assignment.operator {
+                    self.emit(OpCode::Dup2);
+                    self.emit(OpCode::GetIndex);
+                    self.expression(&assignment.value)?;
+                    self.binary_operator(operator);
+                } else {
+                    self.expression(&assignment.value)?;
+                }
+
+                self.emit(OpCode::SetIndex);
+                Ok(())
+            }
+            AssignmentTarget::Property(property) => self.property(property),
+        }
+    }
+
+    /* the arithmetic fails for anything but a number, which the evaluator requires here too */
+    fn increment(&mut self, increment: &Increment) -> CompileResult<()> {
+        let operator = match increment.operator {
+            IncrementOperator::Increment => OpCode::Add,
+            IncrementOperator::Decrement => OpCode::Subtract,
+        };
+        let one = self.constant(Constant::Number(1.0))?;
+
+        match &increment.target {
+            AssignmentTarget::Identifier(variable) => {
+                self.get_variable(variable)?;
+
+                if let Fixity::Postfix = increment.fixity {
+                    self.emit(OpCode::Dup);
+                }
+
+                self.emit_u16(OpCode::Constant, one);
+                self.emit(operator);
+                self.set_variable(variable)?;
+
+                if let Fixity::Postfix = increment.fixity {
+                    self.emit(OpCode::Pop);
+                }
+            }
+            AssignmentTarget::Index(index) => {
+                self.expression(&index.object)?;
+                self.expression(&index.index)?;
+                self.emit(OpCode::Dup2);
+                self.emit(OpCode::GetIndex);
+
+                /* keeps the old value below the list and index, to be left once they are used */
+                if let Fixity::Postfix = increment.fixity {
+                    self.emit(OpCode::Dup);
+                    self.emit_u8(OpCode::Insert, 3);
+                }
+
+                self.emit_u16(OpCode::Constant, one);
+                self.emit(operator);
+                self.emit(OpCode::SetIndex);
+
+                if let Fixity::Postfix = increment.fixity {
+                    self.emit(OpCode::Pop);
+                }
+            }
+            AssignmentTarget::Property(property) => self.property(property)?,
+        }
+
+        Ok(())
+    }
+
+    fn binary(&mut self, binary: &Binary) -> CompileResult<()> {
+        self.expression(&binary.left)?;
+
+        match binary.operator {
+            /* like in lox, the logical operators yield one of their operands rather than a boolean */
+            BinaryOperator::And => {
+                let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit(OpCode::Pop);
+                self.expression(&binary.right)?;
+
+                self.patch_jump(end_jump)
+            }
+            BinaryOperator::Or => {
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                let end_jump = self.emit_jump(OpCode::Jump);
+
+                self.patch_jump(else_jump)?;
+                self.emit(OpCode::Pop);
+                self.expression(&binary.right)?;
+
+                self.patch_jump(end_jump)
+            }
+            _ => {
+                self.expression(&binary.right)?;
+                self.binary_operator(&binary.operator);
+                Ok(())
+            }
+        }
+    }
+
+    fn binary_operator(&mut self, operator: &BinaryOperator) {
+        self.emit(match operator {
+            BinaryOperator::Equality => OpCode::Equal,
+            BinaryOperator::Inequality => OpCode::NotEqual,
+            BinaryOperator::GreaterThan => OpCode::Greater,
+            BinaryOperator::GreaterThanOrEqualTo => OpCode::GreaterEqual,
+            BinaryOperator::LessThan => OpCode::Less,
+            BinaryOperator::LessThanOrEqualTo => OpCode::LessEqual,
+            BinaryOperator::Addition => OpCode::Add,
+            BinaryOperator::Subtraction => OpCode::Subtract,
+            BinaryOperator::Multiplication => OpCode::Multiply,
+            BinaryOperator::Division => OpCode::Divide,
+            BinaryOperator::IntegerDivision => OpCode::IntegerDivide,
+            BinaryOperator::Modulo => OpCode::Modulo,
+            BinaryOperator::Exponentiation => OpCode::Power,
+            BinaryOperator::And | BinaryOperator::Or => {
+                unreachable!("logical operators short-circuit in Compiler::binary")
+            }
+        });
+    }
+
+    fn primary(&mut self, primary: &Primary) -> CompileResult<()> {
+        match primary {
+            Primary::True => self.emit(OpCode::True),
+            Primary::False => self.emit(OpCode::False),
+            Primary::Nil => self.emit(OpCode::Nil),
+            Primary::Number(value) => {
+                let constant = self.constant(Constant::Number(*value))?;
+                self.emit_u16(OpCode::Constant, constant);
+            }
+            Primary::String_(value) => {
+                let constant = self.string(value)?;
+                self.emit_u16(OpCode::Constant, constant);
+            }
+            Primary::Identifier(variable) => self.get_variable(variable)?,
+            Primary::Grouping(expression) => self.expression(expression)?,
+            Primary::List(elements) => {
+                for element in elements {
+                    self.expression(element)?;
+                }
+
+                let count =
+                    u16::try_from(elements.len()).map_err(|_| CompileError::TooManyElements)?;
+                self.emit_u16(OpCode::List, count);
+            }
+            Primary::Map(entries) => {
+                for (key, value) in entries {
+                    self.expression(key)?;
+                    self.expression(value)?;
+                }
+
+                let count =
+                    u16::try_from(entries.len()).map_err(|_| CompileError::TooManyElements)?;
+                self.emit_u16(OpCode::Map, count);
+            }
+            Primary::Function(function) => self.function(function)?,
+        }
+
+        Ok(())
+    }
+
+    fn get_variable(&mut self, variable: &Variable) -> CompileResult<()> {
+        match self.variable(variable)? {
+            VariableLocation::Local(slot) => self.emit_u8(OpCode::GetLocal, slot),
+            VariableLocation::Upvalue(index) => self.emit_u8(OpCode::GetUpvalue, index),
+            VariableLocation::Global(name) => self.emit_u16(OpCode::GetGlobal, name),
+        }
+
+        Ok(())
+    }
+
+    /* leaves the assigned value on the stack */
+    fn set_variable(&mut self, variable: &Variable) -> CompileResult<()> {
+        match self.variable(variable)? {
+            VariableLocation::Local(slot) => self.emit_u8(OpCode::SetLocal, slot),
+            VariableLocation::Upvalue(index) => self.emit_u8(OpCode::SetUpvalue, index),
+            VariableLocation::Global(name) => self.emit_u16(OpCode::SetGlobal, name),
+        }
+
+        Ok(())
+    }
+
+    /* finds the variable the resolver bound a use to, capturing it if it belongs to an
+     * enclosing function */
+    fn variable(&mut self, variable: &Variable) -> CompileResult<VariableLocation> {
+        let Some(local) = variable.local else {
+            return Ok(VariableLocation::Global(
+                self.string(variable.name.as_str())?,
+            ));
+        };
+
+        let scope = &self.scopes[self.scopes.len() - 1 - local.depth];
+        let (function, slot) = (scope.function, scope.base + local.slot);
+        let current = self.functions.len() - 1;
+
+        match function == current {
+            true => Ok(VariableLocation::Local(slot as u8)),
+            false => Ok(VariableLocation::Upvalue(
+                self.upvalue(current, function, slot)?,
+            )),
+        }
+    }
+
+    /* the index of the upvalue through which `function` reaches `slot` of the enclosing
+     * function `owner`, added along with those of the functions in between if necessary */
+    fn upvalue(&mut self, function: usize, owner: usize, slot: usize) -> CompileResult<u8> {
+        let source = match function - 1 == owner {
+            true => {
+                self.functions[owner].captured[slot] = true;
+                UpvalueSource::Local(slot as u8)
+            }
+            false => UpvalueSource::Upvalue(self.upvalue(function - 1, owner, slot)?),
+        };
+
+        let upvalues = &mut self.functions[function].prototype.upvalues;
+
+        let index = match upvalues.iter().position(|&upvalue| upvalue == source) {
+            Some(index) => index,
+            None => {
+                upvalues.push(source);
+                upvalues.len() - 1
+            }
+        };
+
+        u8::try_from(index).map_err(|_| CompileError::TooManyUpvalues)
+    }
+}
+
+enum VariableLocation {
+    Local(u8),
+    Upvalue(u8),
+    /* the constant holding the name */
+    Global(u16),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_source(code: &str) -> Rc<Prototype> {
+        let tokens = lexer::tokenize(code).unwrap();
+        let mut ast = Ast::new(tokens.into_iter()).unwrap();
+        ast.resolve().unwrap();
+
+        compile(&ast).unwrap()
+    }
+
+    /* the opcodes of a chunk, skipping over their operands */
+    fn opcodes(chunk: &Chunk) -> Vec<OpCode> {
+        let mut opcodes = Vec::new();
+        let mut offset = 0;
+
+        while offset < chunk.code.len() {
+            let opcode = OpCode::try_from(chunk.code[offset]).unwrap();
+            offset += 1 + opcode.operand_size();
+            opcodes.push(opcode);
+        }
+
+        opcodes
+    }
+
+    fn function(chunk: &Chunk) -> &Prototype {
+        chunk
+            .constants
+            .iter()
+            .find_map(|constant| match constant {
+                Constant::Function(prototype) => Some(&**prototype),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_locals_live_on_the_stack() {
+        let program = compile_source("var a = 1; { var b = a; print b; }");
+
+        assert_eq!(
+            opcodes(&program.chunk),
+            [
+                OpCode::Constant,
+                OpCode::DefineGlobal,
+                OpCode::GetGlobal,
+                OpCode::GetLocal,
+                OpCode::Print,
+                OpCode::Pop,
+                OpCode::Nil,
+                OpCode::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_captured_locals_are_closed() {
+        let program = compile_source("{ var a = 1; var b = 2; var f = () => a; }");
+
+        assert_eq!(
+            opcodes(&program.chunk)[..7],
+            [
+                OpCode::Constant,
+                OpCode::Constant,
+                OpCode::Closure,
+                OpCode::Pop,
+                OpCode::Pop,
+                OpCode::CloseUpvalue,
+                OpCode::Nil,
+            ]
+        );
+        assert_eq!(function(&program.chunk).upvalues, [UpvalueSource::Local(0)]);
+    }
+
+    #[test]
+    fn test_upvalues_through_enclosing_functions() {
+        let program =
+            compile_source("fun f() { var a; var b; fun g() { fun h() { return b + a + b; } } }");
+
+        let g = function(&function(&program.chunk).chunk);
+        let h = function(&g.chunk);
+
+        assert_eq!(
+            g.upvalues,
+            [UpvalueSource::Local(1), UpvalueSource::Local(0)]
+        );
+        assert_eq!(
+            h.upvalues,
+            [UpvalueSource::Upvalue(0), UpvalueSource::Upvalue(1)]
+        );
+    }
+
+    #[test]
+    fn test_unresolved_ast() {
+        let tokens = lexer::tokenize("{ var a = 1; print a; }").unwrap();
+        let ast = Ast::new(tokens.into_iter()).unwrap();
+
+        assert!(matches!(compile(&ast), Err(CompileError::UnresolvedAst)));
+    }
+
+    #[test]
+    fn test_constants_are_shared() {
+        let program = compile_source("var a = \"a\"; print a + \"a\"; print 1 + 1;");
+
+        assert_eq!(program.chunk.constants.len(), 3);
+    }
+}
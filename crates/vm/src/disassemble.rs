@@ -0,0 +1,182 @@
+use crate::chunk::{Chunk, Constant, OpCode, Prototype, UpvalueSource};
+
+use std::fmt::Write;
+
+/// Renders a compiled program as text, one instruction per line with its offset, source line,
+/// opcode and operands. The functions it defines follow it, each under its own heading.
+pub fn disassemble(prototype: &Prototype) -> String {
+    let mut output = String::new();
+    write_prototype(&mut output, prototype, "<script>");
+
+    output
+}
+
+/* `unnamed` heads a prototype without a name, which is either the program or a function
+ * expression */
+fn write_prototype(output: &mut String, prototype: &Prototype, unnamed: &str) {
+    writeln!(
+        output,
+        "== {} ==",
+        prototype.name.as_deref().unwrap_or(unnamed)
+    )
+    .unwrap();
+
+    let chunk = &prototype.chunk;
+    let mut offset = 0;
+
+    while offset < chunk.code.len() {
+        offset = write_instruction(output, chunk, offset);
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(function) = constant {
+            writeln!(output).unwrap();
+            write_prototype(output, function, "<fn>");
+        }
+    }
+}
+
+/* writes the instruction at `offset`, returning the offset of the next one */
+fn write_instruction(output: &mut String, chunk: &Chunk, offset: usize) -> usize {
+    let line = chunk.line(offset);
+
+    /* a line that continues the previous instruction's is shown as a bar, as in clox */
+    match offset > 0 && chunk.line(offset - 1) == line {
+        true => write!(output, "{offset:04}    | ").unwrap(),
+        false => write!(output, "{offset:04} {line:>4} ").unwrap(),
+    }
+
+    let Ok(opcode) = OpCode::try_from(chunk.code[offset]) else {
+        writeln!(output, "<unknown opcode {}>", chunk.code[offset]).unwrap();
+        return offset + 1;
+    };
+
+    let next = offset + 1 + opcode.operand_size();
+
+    if next > chunk.code.len() {
+        writeln!(output, "{:<16} <truncated>", format!("{opcode:?}")).unwrap();
+        return chunk.code.len();
+    }
+
+    let operands = match opcode {
+        OpCode::Constant
+        | OpCode::DefineGlobal
+        | OpCode::GetGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty => {
+            let index = chunk.read_u16(offset + 1);
+            format!("{index:4} {}", constant(chunk, index))
+        }
+        OpCode::Closure => {
+            let index = chunk.read_u16(offset + 1);
+            let upvalues = match chunk.constants.get(index as usize) {
+                Some(Constant::Function(function)) => function
+                    .upvalues
+                    .iter()
+                    .map(|upvalue| match upvalue {
+                        UpvalueSource::Local(slot) => format!("local {slot}"),
+                        UpvalueSource::Upvalue(index) => format!("upvalue {index}"),
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            format!(
+                "{index:4} {} [{}]",
+                constant(chunk, index),
+                upvalues.join(", ")
+            )
+        }
+        OpCode::Jump | OpCode::JumpIfFalse => {
+            let target = next + chunk.read_u16(offset + 1) as usize;
+            format!("{offset:04} -> {target:04}")
+        }
+        OpCode::Loop => {
+            let target = next.saturating_sub(chunk.read_u16(offset + 1) as usize);
+            format!("{offset:04} -> {target:04}")
+        }
+        OpCode::List | OpCode::Map => format!("{:4}", chunk.read_u16(offset + 1)),
+        _ => match opcode.operand_size() {
+            1 => format!("{:4}", chunk.read_u8(offset + 1)),
+            _ => String::new(),
+        },
+    };
+
+    match operands.is_empty() {
+        true => writeln!(output, "{opcode:?}").unwrap(),
+        false => writeln!(output, "{:<16} {operands}", format!("{opcode:?}")).unwrap(),
+    }
+
+    next
+}
+
+fn constant(chunk: &Chunk, index: u16) -> String {
+    match chunk.constants.get(index as usize) {
+        Some(Constant::Number(value)) => format!("{value}"),
+        Some(Constant::String_(value)) => format!("{value:?}"),
+        Some(Constant::Function(function)) => match &function.name {
+            Some(name) => format!("<fn {name}>"),
+            None => String::from("<fn>"),
+        },
+        None => String::from("<missing constant>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disassemble_source(code: &str) -> String {
+        let tokens = lexer::tokenize(code).unwrap();
+        let mut ast = parser::Ast::new(tokens.into_iter()).unwrap();
+        ast.resolve().unwrap();
+
+        disassemble(&crate::compile(&ast).unwrap())
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let listing = disassemble_source(
+            "var a = 1;\nwhile (a < 3)\n    a = a + 1;\nfun f(b) {\n    return () => a + b;\n}\n",
+        );
+
+        assert_eq!(
+            listing,
+            "\
+== <script> ==
+0000    1 Constant            0 1
+0003    | DefineGlobal        1 \"a\"
+0006    2 GetGlobal           1 \"a\"
+0009    | Constant            2 3
+0012    | Less
+0013    | JumpIfFalse      0013 -> 0031
+0016    | Pop
+0017    | GetGlobal           1 \"a\"
+0020    | Constant            3 1
+0023    | Add
+0024    | SetGlobal           1 \"a\"
+0027    | Pop
+0028    | Loop             0028 -> 0006
+0031    | Pop
+0032    4 Closure             4 <fn f> []
+0035    | DefineGlobal        5 \"f\"
+0038    | Nil
+0039    | Return
+
+== f ==
+0000    5 Closure             0 <fn> [local 0]
+0003    | Return
+0004    4 Nil
+0005    | Return
+
+== <fn> ==
+0000    5 GetGlobal           0 \"a\"
+0003    | GetUpvalue          0
+0005    | Add
+0006    | Return
+0007    | Nil
+0008    | Return
+"
+        );
+    }
+}
@@ -0,0 +1,13 @@
+mod chunk;
+mod compiler;
+mod disassemble;
+mod serialize;
+mod value;
+mod vm;
+
+pub use chunk::{Chunk, Constant, OpCode, Prototype, UpvalueSource};
+pub use compiler::{CompileError, compile};
+pub use disassemble::disassemble;
+pub use serialize::{FORMAT_VERSION, LoadError, MAGIC, is_serialized, load, save};
+pub use value::{Closure, MapKey, NativeFn, NativeFunction, Upvalue, Value};
+pub use vm::Vm;
@@ -0,0 +1,498 @@
+use crate::chunk::{Chunk, Constant, OpCode, Prototype, UpvalueSource};
+
+use std::io::{Read, Write};
+use std::rc::Rc;
+
+/// The bytes every serialized program starts with. The first is not printable, so a program's
+/// source can never be mistaken for one.
+pub const MAGIC: &[u8; 4] = b"\x7flox";
+
+/// The version of the serialized format, written after the magic bytes. It changes whenever the
+/// layout or the instruction set does, and loading a program of another version fails.
+pub const FORMAT_VERSION: u16 = 1;
+
+/* functions nest no deeper than this in a loadable program, so that a hostile file can't
+ * overflow the stack of the recursive loader */
+const MAX_NESTING: usize = 256;
+
+#[derive(Debug)]
+pub enum LoadError {
+    NotAProgram,
+    UnsupportedVersion(u16),
+    Malformed,
+    Io(std::io::Error),
+}
+
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => LoadError::Malformed,
+            _ => LoadError::Io(error),
+        }
+    }
+}
+
+type LoadResult<T> = Result<T, LoadError>;
+
+/// Whether `bytes` begin like a serialized program rather than source code.
+pub fn is_serialized(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Writes a compiled program in the versioned binary format, so that it can be loaded again
+/// without lexing, parsing and compiling its source.
+///
+/// Integers are little-endian. A prototype is its optional name, its arity, its upvalue sources
+/// and its chunk; a chunk is its code, its line runs and its constants, where function constants
+/// nest their prototypes.
+pub fn save(prototype: &Prototype, writer: &mut impl Write) -> std::io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+
+    write_prototype(prototype, writer)
+}
+
+/// Reads a program written by [`save`]. The program is checked to be well formed: its
+/// instructions decode, refer to constants of the right kind and upvalues that exist, and jump
+/// within their chunk. How instructions use the stack is not checked here, so a corrupt program
+/// may still load and then fail with `RuntimeError::MalformedProgram` when it runs.
+pub fn load(reader: &mut impl Read) -> LoadResult<Rc<Prototype>> {
+    let mut magic = [0; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|error| match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => LoadError::NotAProgram,
+            _ => LoadError::Io(error),
+        })?;
+
+    if &magic != MAGIC {
+        return Err(LoadError::NotAProgram);
+    }
+
+    let version = u16::from_le_bytes(read_array(reader)?);
+    if version != FORMAT_VERSION {
+        return Err(LoadError::UnsupportedVersion(version));
+    }
+
+    let prototype = read_prototype(reader, 0, 0)?;
+
+    /* nothing may follow the program */
+    match reader.read(&mut [0])? {
+        0 => Ok(Rc::new(prototype)),
+        _ => Err(LoadError::Malformed),
+    }
+}
+
+fn write_prototype(prototype: &Prototype, writer: &mut impl Write) -> std::io::Result<()> {
+    match &prototype.name {
+        Some(name) => {
+            writer.write_all(&[1])?;
+            write_string(name, writer)?;
+        }
+        None => writer.write_all(&[0])?,
+    }
+
+    write_u32(prototype.arity, writer)?;
+
+    write_u32(prototype.upvalues.len(), writer)?;
+    for upvalue in &prototype.upvalues {
+        match upvalue {
+            UpvalueSource::Local(slot) => writer.write_all(&[0, *slot])?,
+            UpvalueSource::Upvalue(index) => writer.write_all(&[1, *index])?,
+        }
+    }
+
+    let chunk = &prototype.chunk;
+
+    write_u32(chunk.code.len(), writer)?;
+    writer.write_all(&chunk.code)?;
+
+    write_u32(chunk.lines.len(), writer)?;
+    for &(offset, line) in &chunk.lines {
+        write_u32(offset, writer)?;
+        write_u32(line, writer)?;
+    }
+
+    write_u32(chunk.constants.len(), writer)?;
+    for constant in &chunk.constants {
+        match constant {
+            Constant::Number(value) => {
+                writer.write_all(&[0])?;
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            Constant::String_(value) => {
+                writer.write_all(&[1])?;
+                write_string(value, writer)?;
+            }
+            Constant::Function(function) => {
+                writer.write_all(&[2])?;
+                write_prototype(function, writer)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/* lengths and line numbers are written as 32 bits, which no compiled program outgrows */
+fn write_u32(value: usize, writer: &mut impl Write) -> std::io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| std::io::ErrorKind::InvalidInput)?;
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_string(value: &str, writer: &mut impl Write) -> std::io::Result<()> {
+    write_u32(value.len(), writer)?;
+    writer.write_all(value.as_bytes())
+}
+
+/* `enclosing_upvalues` is how many upvalues the enclosing function has, which the prototype's
+ * own upvalues may refer to */
+fn read_prototype(
+    reader: &mut impl Read,
+    nesting: usize,
+    enclosing_upvalues: usize,
+) -> LoadResult<Prototype> {
+    if nesting > MAX_NESTING {
+        return Err(LoadError::Malformed);
+    }
+
+    let name = match read_u8(reader)? {
+        0 => None,
+        1 => Some(read_string(reader)?),
+        _ => return Err(LoadError::Malformed),
+    };
+
+    let arity = read_u32(reader)?;
+    if arity > u8::MAX as usize {
+        return Err(LoadError::Malformed);
+    }
+
+    let upvalue_count = read_u32(reader)?;
+    if upvalue_count > u8::MAX as usize + 1 {
+        return Err(LoadError::Malformed);
+    }
+
+    let mut upvalues = Vec::with_capacity(upvalue_count);
+    for _ in 0..upvalue_count {
+        let [kind, index] = read_array(reader)?;
+
+        upvalues.push(match kind {
+            0 => UpvalueSource::Local(index),
+            1 if (index as usize) < enclosing_upvalues => UpvalueSource::Upvalue(index),
+            _ => return Err(LoadError::Malformed),
+        });
+    }
+
+    let code = read_bytes(reader)?;
+
+    let line_count = read_u32(reader)?;
+    let mut lines = Vec::new();
+    for _ in 0..line_count {
+        lines.push((read_u32(reader)?, read_u32(reader)?));
+    }
+
+    let constant_count = read_u32(reader)?;
+    if constant_count > u16::MAX as usize + 1 {
+        return Err(LoadError::Malformed);
+    }
+
+    let mut constants = Vec::new();
+    for _ in 0..constant_count {
+        constants.push(match read_u8(reader)? {
+            0 => Constant::Number(f64::from_le_bytes(read_array(reader)?)),
+            1 => Constant::String_(Rc::from(read_string(reader)?)),
+            2 => Constant::Function(Rc::new(read_prototype(reader, nesting + 1, upvalue_count)?)),
+            _ => return Err(LoadError::Malformed),
+        });
+    }
+
+    let prototype = Prototype {
+        name,
+        arity,
+        upvalues,
+        chunk: Chunk {
+            code,
+            constants,
+            lines,
+        },
+    };
+
+    verify(&prototype)?;
+    Ok(prototype)
+}
+
+/* checks that a prototype's code decodes and that its operands refer to what they should, which
+ * lets a corrupt file fail on load rather than partway through running */
+fn verify(prototype: &Prototype) -> LoadResult<()> {
+    let chunk = &prototype.chunk;
+
+    /* line runs start at the beginning of the code and go forwards */
+    let starts_at_zero = chunk.lines.first().is_none_or(|&(offset, _)| offset == 0);
+    let ascending = chunk.lines.windows(2).all(|runs| runs[0].0 < runs[1].0);
+    if !starts_at_zero || !ascending {
+        return Err(LoadError::Malformed);
+    }
+
+    /* the first pass finds where instructions start, which jumps must land on */
+    let mut starts = vec![false; chunk.code.len()];
+    let mut offset = 0;
+    let mut last = None;
+
+    while offset < chunk.code.len() {
+        let opcode = OpCode::try_from(chunk.code[offset]).map_err(|_| LoadError::Malformed)?;
+
+        starts[offset] = true;
+        last = Some(opcode);
+        offset += 1 + opcode.operand_size();
+    }
+
+    /* execution must not run off the end of the code, so it ends with a return */
+    if offset != chunk.code.len() || last != Some(OpCode::Return) {
+        return Err(LoadError::Malformed);
+    }
+
+    let constant = |offset: usize| chunk.constants.get(chunk.read_u16(offset) as usize);
+    let lands =
+        |target: Option<usize>| target.is_some_and(|target| starts.get(target) == Some(&true));
+
+    let mut offset = 0;
+
+    while offset < chunk.code.len() {
+        let opcode = OpCode::try_from(chunk.code[offset]).unwrap();
+        let next = offset + 1 + opcode.operand_size();
+
+        let valid = match opcode {
+            OpCode::Constant => matches!(
+                constant(offset + 1),
+                Some(Constant::Number(_) | Constant::String_(_))
+            ),
+            OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal | OpCode::GetProperty => {
+                matches!(constant(offset + 1), Some(Constant::String_(_)))
+            }
+            OpCode::Closure => matches!(constant(offset + 1), Some(Constant::Function(_))),
+            OpCode::GetUpvalue | OpCode::SetUpvalue => {
+                (chunk.read_u8(offset + 1) as usize) < prototype.upvalues.len()
+            }
+            OpCode::Jump | OpCode::JumpIfFalse => {
+                lands(Some(next + chunk.read_u16(offset + 1) as usize))
+            }
+            OpCode::Loop => lands(next.checked_sub(chunk.read_u16(offset + 1) as usize)),
+            _ => true,
+        };
+
+        if !valid {
+            return Err(LoadError::Malformed);
+        }
+
+        offset = next;
+    }
+
+    Ok(())
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> LoadResult<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn read_u8(reader: &mut impl Read) -> LoadResult<u8> {
+    Ok(read_array::<1>(reader)?[0])
+}
+
+fn read_u32(reader: &mut impl Read) -> LoadResult<usize> {
+    Ok(u32::from_le_bytes(read_array(reader)?) as usize)
+}
+
+/* a length followed by that many bytes. the bytes are read as they arrive rather than
+ * allocated up front, so that a corrupt length can't exhaust memory */
+fn read_bytes(reader: &mut impl Read) -> LoadResult<Vec<u8>> {
+    let length = read_u32(reader)?;
+
+    let mut bytes = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+
+    match bytes.len() == length {
+        true => Ok(bytes),
+        false => Err(LoadError::Malformed),
+    }
+}
+
+fn read_string(reader: &mut impl Read) -> LoadResult<String> {
+    String::from_utf8(read_bytes(reader)?).map_err(|_| LoadError::Malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Vm;
+
+    use evaluator::{Limits, OutputBuffer, RuntimeError};
+
+    fn compile_source(code: &str) -> Rc<Prototype> {
+        let tokens = lexer::tokenize(code).unwrap();
+        let mut ast = parser::Ast::new(tokens.into_iter()).unwrap();
+        ast.resolve().unwrap();
+
+        crate::compile(&ast).unwrap()
+    }
+
+    fn saved(prototype: &Prototype) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        save(prototype, &mut bytes).unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = compile_source(
+            "var a = 1.5;\nfun f(b) {\n    return () => a + b;\n}\nprint f(2)();\nprint [1, 2][1];",
+        );
+        let bytes = saved(&program);
+        assert!(is_serialized(&bytes));
+
+        let loaded = load(&mut bytes.as_slice()).unwrap();
+        assert_eq!(crate::disassemble(&loaded), crate::disassemble(&program));
+        assert_eq!(saved(&loaded), bytes);
+
+        let mut vm = Vm::new();
+        let output = OutputBuffer::new();
+        vm.set_output(output.clone());
+
+        vm.run(loaded).unwrap();
+        assert_eq!(output.contents(), "3.5\n2\n");
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert!(matches!(
+            load(&mut "print 1;".as_bytes()),
+            Err(LoadError::NotAProgram)
+        ));
+        assert!(matches!(
+            load(&mut &b"\x7f"[..]),
+            Err(LoadError::NotAProgram)
+        ));
+
+        let mut bytes = saved(&compile_source("print 1;"));
+        bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            load(&mut bytes.as_slice()),
+            Err(LoadError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_rejects_malformed_programs() {
+        let bytes = saved(&compile_source(
+            "var a = 1; while (a < 3) a = a + 1; print a;",
+        ));
+
+        /* every truncation, and trailing bytes */
+        for length in 6..bytes.len() {
+            assert!(matches!(
+                load(&mut &bytes[..length]),
+                Err(LoadError::Malformed)
+            ));
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            load(&mut trailing.as_slice()),
+            Err(LoadError::Malformed)
+        ));
+
+        /* a constant index out of range, an unknown opcode and a jump into an operand */
+        let program = compile_source("var a = 1; while (a < 3) a = a + 1;");
+        let corruptions: [fn(&mut Chunk); 3] = [
+            |chunk| chunk.code[1..3].copy_from_slice(&u16::MAX.to_be_bytes()),
+            |chunk| chunk.code[0] = u8::MAX,
+            |chunk| {
+                let jump = chunk
+                    .code
+                    .iter()
+                    .position(|&byte| byte == OpCode::JumpIfFalse as u8)
+                    .unwrap();
+                chunk.code[jump + 1..jump + 3].copy_from_slice(&2u16.to_be_bytes());
+            },
+        ];
+
+        for corrupt in corruptions {
+            let mut prototype = (*program).clone();
+            corrupt(&mut prototype.chunk);
+
+            assert!(matches!(
+                load(&mut saved(&prototype).as_slice()),
+                Err(LoadError::Malformed)
+            ));
+        }
+    }
+
+    /* programs that load but misuse the stack fail when run instead of panicking */
+    #[test]
+    fn test_runs_corrupt_programs() {
+        let mut program = (*compile_source("print 1;")).clone();
+        program.chunk.code = vec![
+            OpCode::Pop as u8,
+            OpCode::Pop as u8,
+            OpCode::Nil as u8,
+            OpCode::Return as u8,
+        ];
+
+        let loaded = load(&mut saved(&program).as_slice()).unwrap();
+        assert!(matches!(
+            Vm::new().run(loaded),
+            Err(RuntimeError::MalformedProgram)
+        ));
+
+        /* every byte of a function's code, set to every value */
+        let program = compile_source(
+            "var l = [1]; fun f(a) { var b = {a: l}; return () => a + b[a][0]; } print f(1)();",
+        );
+        let Some(Constant::Function(function)) = program
+            .chunk
+            .constants
+            .iter()
+            .find(|constant| matches!(constant, Constant::Function(_)))
+        else {
+            panic!("expected a function constant");
+        };
+
+        for target in [&program, function] {
+            for offset in 0..target.chunk.code.len() {
+                for byte in 0..=u8::MAX {
+                    let mut corrupt = (**target).clone();
+                    corrupt.chunk.code[offset] = byte;
+
+                    let mut program = (*program).clone();
+                    if std::ptr::eq(target, function) {
+                        for constant in &mut program.chunk.constants {
+                            if let Constant::Function(_) = constant {
+                                *constant = Constant::Function(Rc::new(corrupt.clone()));
+                            }
+                        }
+                    } else {
+                        program = corrupt;
+                    }
+
+                    let Ok(loaded) = load(&mut saved(&program).as_slice()) else {
+                        continue;
+                    };
+
+                    let mut vm = Vm::new();
+                    vm.set_output(OutputBuffer::new());
+                    vm.set_limits(Limits {
+                        max_steps: Some(10_000),
+                        ..Limits::default()
+                    });
+
+                    let _ = vm.run(loaded);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::chunk::Prototype;
+
+use evaluator::{Arity, Host, NativeValue, RuntimeError};
+
+use indexmap::IndexMap;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A value on the machine's stack. Strings are shared rather than copied when a value is read,
+/// and lists, maps and closures are shared like they are in the tree-walking evaluator.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Numeric(f64),
+    String_(Rc<str>),
+    Boolean(bool),
+    NativeFunction(NativeFunction),
+    Function(Rc<Closure>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<IndexMap<MapKey, Value>>>),
+    Nil,
+}
+
+/// The subset of values that may key a map. Entries keep their insertion order.
+#[derive(Debug, Clone)]
+pub enum MapKey {
+    Numeric(f64),
+    String_(Rc<str>),
+    Boolean(bool),
+    Nil,
+}
+
+impl Value {
+    pub fn list(elements: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn map(entries: IndexMap<MapKey, Value>) -> Self {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    /// `nil` and `false` are falsey, everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Boolean(false) | Value::Nil)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Numeric(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String_(Rc::from(value))
+    }
+}
+
+/* lets the machine share the evaluator's standard library */
+impl NativeValue for Value {
+    type Key = MapKey;
+
+    fn nil() -> Self {
+        Value::Nil
+    }
+
+    fn list(elements: Vec<Self>) -> Self {
+        Value::list(elements)
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Numeric(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String_(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn as_list(&self) -> Option<&RefCell<Vec<Self>>> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    fn as_map(&self) -> Option<&RefCell<IndexMap<MapKey, Self>>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+/* the same equality as the evaluator's: values of different types are never equal, and lists,
+ * maps and functions are equal only to themselves */
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Numeric(left), Value::Numeric(right)) => left == right,
+            (Value::String_(left), Value::String_(right)) => left == right,
+            (Value::Boolean(left), Value::Boolean(right)) => left == right,
+            (Value::NativeFunction(left), Value::NativeFunction(right)) => {
+                Rc::ptr_eq(&left.function, &right.function)
+            }
+            (Value::Function(left), Value::Function(right)) => Rc::ptr_eq(left, right),
+            (Value::List(left), Value::List(right)) => Rc::ptr_eq(left, right),
+            (Value::Map(left), Value::Map(right)) => Rc::ptr_eq(left, right),
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+pub type NativeFn = dyn Fn(&mut dyn Host, &[Value]) -> Result<Value, RuntimeError>;
+
+/// A function implemented in Rust.
+#[derive(Clone)]
+pub struct NativeFunction {
+    name: Rc<str>,
+    arity: Arity,
+    function: Rc<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut dyn Host, &[Value]) -> Result<Value, RuntimeError> + 'static,
+    ) -> Self {
+        NativeFunction {
+            name: Rc::from(name),
+            arity,
+            function: Rc::new(function),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arity(&self) -> Arity {
+        self.arity
+    }
+
+    pub fn call(&self, host: &mut dyn Host, arguments: &[Value]) -> Result<Value, RuntimeError> {
+        (self.function)(host, arguments)
+    }
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A function prototype together with the variables it captured from enclosing functions.
+#[derive(Debug)]
+pub struct Closure {
+    pub prototype: Rc<Prototype>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+/// A captured variable. It is open while the variable still lives in its slot on the stack and
+/// is closed over a copy of the value once the variable goes out of scope, so that every closure
+/// sharing it sees the same variable either way.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_value(f, self, &mut Vec::new())
+    }
+}
+
+/* `printing` holds the lists and maps that are being printed, so that a collection containing
+ * itself prints `[...]` or `{...}` where it repeats instead of recursing forever */
+fn write_value(
+    f: &mut std::fmt::Formatter<'_>,
+    value: &Value,
+    printing: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match value {
+        Value::Numeric(value) => write!(f, "{value}"),
+        Value::String_(value) => write!(f, "{value}"),
+        Value::Boolean(value) => write!(f, "{value}"),
+        Value::NativeFunction(native) => write!(f, "<native fn {}>", native.name()),
+        Value::Function(closure) => match &closure.prototype.name {
+            Some(name) => write!(f, "<fn {name}>"),
+            None => write!(f, "<fn>"),
+        },
+        Value::List(list) => write_collection(
+            f,
+            Rc::as_ptr(list) as _,
+            "[...]",
+            printing,
+            |f, printing| {
+                write!(f, "[")?;
+
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write_nested(f, element, printing)?;
+                }
+
+                write!(f, "]")
+            },
+        ),
+        Value::Map(map) => {
+            write_collection(f, Rc::as_ptr(map) as _, "{...}", printing, |f, printing| {
+                write!(f, "{{")?;
+
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write_nested(f, &Value::from(key.clone()), printing)?;
+                    write!(f, ": ")?;
+                    write_nested(f, value, printing)?;
+                }
+
+                write!(f, "}}")
+            })
+        }
+        Value::Nil => write!(f, "nil"),
+    }
+}
+
+fn write_collection(
+    f: &mut std::fmt::Formatter<'_>,
+    address: *const (),
+    repeated: &str,
+    printing: &mut Vec<*const ()>,
+    write: impl FnOnce(&mut std::fmt::Formatter<'_>, &mut Vec<*const ()>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    if printing.contains(&address) {
+        return write!(f, "{repeated}");
+    }
+
+    printing.push(address);
+    write(f, printing)?;
+    printing.pop();
+
+    Ok(())
+}
+
+/* strings inside collections are quoted so that `["a, b"]` and `["a", "b"]` print differently */
+fn write_nested(
+    f: &mut std::fmt::Formatter<'_>,
+    value: &Value,
+    printing: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match value {
+        Value::String_(value) => write!(f, "{value:?}"),
+        _ => write_value(f, value, printing),
+    }
+}
+
+impl MapKey {
+    /* the memory the key takes up in a map */
+    pub(crate) fn allocation(&self) -> usize {
+        match self {
+            MapKey::String_(string) => std::mem::size_of::<MapKey>() + string.len(),
+            _ => std::mem::size_of::<MapKey>(),
+        }
+    }
+}
+
+impl TryFrom<Value> for MapKey {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            /* -0 and 0 compare equal, so they must also hash equally */
+            Value::Numeric(0.0) => Ok(MapKey::Numeric(0.0)),
+            Value::Numeric(value) => Ok(MapKey::Numeric(value)),
+            Value::String_(value) => Ok(MapKey::String_(value)),
+            Value::Boolean(value) => Ok(MapKey::Boolean(value)),
+            Value::Nil => Ok(MapKey::Nil),
+            _ => Err(RuntimeError::UnhashableKey),
+        }
+    }
+}
+
+impl From<MapKey> for Value {
+    fn from(key: MapKey) -> Self {
+        match key {
+            MapKey::Numeric(value) => Value::Numeric(value),
+            MapKey::String_(value) => Value::String_(value),
+            MapKey::Boolean(value) => Value::Boolean(value),
+            MapKey::Nil => Value::Nil,
+        }
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MapKey::Numeric(left), MapKey::Numeric(right)) => left.to_bits() == right.to_bits(),
+            (MapKey::String_(left), MapKey::String_(right)) => left == right,
+            (MapKey::Boolean(left), MapKey::Boolean(right)) => left == right,
+            (MapKey::Nil, MapKey::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MapKey {}
+
+impl std::hash::Hash for MapKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+
+        match self {
+            MapKey::Numeric(value) => value.to_bits().hash(state),
+            MapKey::String_(value) => value.hash(state),
+            MapKey::Boolean(value) => value.hash(state),
+            MapKey::Nil => (),
+        }
+    }
+}
@@ -0,0 +1,1076 @@
+use crate::chunk::{Constant, OpCode, Prototype, UpvalueSource};
+use crate::value::{Closure, MapKey, NativeFunction, Upvalue, Value};
+
+use evaluator::{
+    Arity, Host, InterruptHandle, Limits, Natives, RuntimeError, System, TimeSource, Usage,
+};
+
+use indexmap::IndexMap;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+use std::time::Instant;
+
+type VmResult<T> = Result<T, RuntimeError>;
+
+/// A stack machine that runs compiled programs. Globals persist from one program to the next, so
+/// that a REPL can run each input as a program of its own.
+pub struct Vm {
+    stack: Vec<Value>,
+    /* the frames of the functions waiting on a call. the running one is kept apart */
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    /* the globals that programs may read but not assign to, such as PI */
+    constants: HashSet<Rc<str>>,
+    /* the upvalues still pointing into the stack, ordered by the slot they point to */
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    system: System,
+    limits: Limits,
+    usage: Usage,
+    interrupt: InterruptHandle,
+}
+
+/* what a native sees of the machine while it runs */
+struct Context<'a> {
+    system: &'a mut System,
+    limits: &'a Limits,
+    usage: &'a mut Usage,
+    globals: &'a HashMap<Rc<str>, Value>,
+    stack: &'a [Value],
+}
+
+impl Host for Context<'_> {
+    fn system(&mut self) -> &mut System {
+        self.system
+    }
+
+    fn allocate(&mut self, bytes: usize) -> VmResult<()> {
+        allocate(self.limits, self.usage, self.globals, self.stack, bytes)
+    }
+}
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    /* the offset of the next instruction to run */
+    ip: usize,
+    /* the stack index of the first argument, which is local slot 0 */
+    base: usize,
+}
+
+impl CallFrame {
+    fn read_u8(&mut self) -> VmResult<u8> {
+        let chunk = &self.closure.prototype.chunk;
+        let byte = *chunk
+            .code
+            .get(self.ip)
+            .ok_or(RuntimeError::MalformedProgram)?;
+
+        self.ip += 1;
+        Ok(byte)
+    }
+
+    fn read_u16(&mut self) -> VmResult<u16> {
+        Ok(u16::from_be_bytes([self.read_u8()?, self.read_u8()?]))
+    }
+
+    fn read_constant(&mut self) -> VmResult<&Constant> {
+        let index = self.read_u16()? as usize;
+
+        self.closure
+            .prototype
+            .chunk
+            .constants
+            .get(index)
+            .ok_or(RuntimeError::MalformedProgram)
+    }
+
+    /* the name operand of a global or property instruction */
+    fn read_name(&mut self) -> VmResult<Rc<str>> {
+        match self.read_constant()? {
+            Constant::String_(name) => Ok(name.clone()),
+            _ => Err(RuntimeError::MalformedProgram),
+        }
+    }
+}
+
+impl Vm {
+    /// How deeply calls may nest whatever the limits say. Frames live on the heap rather than on
+    /// the native stack, so this only keeps runaway recursion from using up all memory.
+    pub const MAX_FRAMES: usize = 1 << 18;
+
+    pub fn new() -> Self {
+        let mut vm = Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            constants: HashSet::new(),
+            open_upvalues: Vec::new(),
+            system: System::new(),
+            limits: Limits::default(),
+            usage: Usage::default(),
+            interrupt: InterruptHandle::default(),
+        };
+
+        evaluator::standard_library(&mut vm);
+
+        vm
+    }
+
+    /// Redirects the output of `print` statements, which goes to stdout by default.
+    pub fn set_output(&mut self, sink: impl Write + 'static) {
+        self.system.set_output(sink)
+    }
+
+    /// Redirects the output of the `eprint` native, which goes to stderr by default.
+    pub fn set_error_output(&mut self, sink: impl Write + 'static) {
+        self.system.set_error_output(sink)
+    }
+
+    /// Replaces where the `readLine` native reads from, which is stdin by default.
+    pub fn set_input(&mut self, source: impl BufRead + 'static) {
+        self.system.set_input(source)
+    }
+
+    /// Replaces the time source of the `time` and `clock` natives, which is the system clock by
+    /// default.
+    pub fn set_time_source(&mut self, source: impl TimeSource + 'static) {
+        self.system.set_time_source(source)
+    }
+
+    /// Allows or denies the filesystem natives, as `Evaluator::set_filesystem_access` does.
+    pub fn set_filesystem_access(&mut self, allowed: bool) {
+        self.system.set_filesystem_access(allowed)
+    }
+
+    /// Allows or denies the console natives, as `Evaluator::set_console_access` does.
+    pub fn set_console_access(&mut self, allowed: bool) {
+        self.system.set_console_access(allowed)
+    }
+
+    /// Bounds the work each run may do. A run that goes over a limit fails with
+    /// `StepLimitExceeded`, `Timeout`, `CallDepthExceeded` or `MemoryLimitExceeded`, like an
+    /// evaluation does.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// A handle that stops the program running, or the next one to run, with
+    /// `RuntimeError::Interrupted`.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    /// Defines a global native function, which programs run afterwards can call.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut dyn Host, &[Value]) -> VmResult<Value> + 'static,
+    ) -> VmResult<()> {
+        let native = NativeFunction::new(name, arity, function);
+        self.define_global(Rc::from(name), Value::NativeFunction(native))
+    }
+
+    fn define_global(&mut self, name: Rc<str>, value: Value) -> VmResult<()> {
+        if self.globals.contains_key(&name) {
+            return Err(RuntimeError::VariableRedefinition);
+        }
+
+        self.globals.insert(name, value);
+        Ok(())
+    }
+
+    /// Runs a compiled program. After an error the machine is left ready to run another.
+    pub fn run(&mut self, program: Rc<Prototype>) -> VmResult<()> {
+        self.start();
+
+        let closure = Rc::new(Closure {
+            prototype: program,
+            upvalues: Vec::new(),
+        });
+
+        self.stack.push(Value::Function(closure.clone()));
+        let frame = CallFrame {
+            closure,
+            ip: 0,
+            base: 1,
+        };
+
+        let result = self.execute(frame);
+
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+
+        result
+    }
+
+    /* hands a new run the full budget of every limit */
+    fn start(&mut self) {
+        self.interrupt.clear();
+        self.usage = Usage {
+            steps: 0,
+            deadline: self.limits.timeout.map(|timeout| Instant::now() + timeout),
+            memory: match self.limits.max_memory {
+                Some(_) => memory_usage(&self.globals, &self.stack),
+                None => 0,
+            },
+        };
+    }
+
+    /* accounts for the execution of one instruction */
+    fn step(&mut self) -> VmResult<()> {
+        self.usage.steps += 1;
+
+        match self.limits.max_steps {
+            Some(max_steps) if self.usage.steps > max_steps => Err(RuntimeError::StepLimitExceeded),
+            _ => Ok(()),
+        }
+    }
+
+    /* fails if the run has been interrupted or is out of time. a program can only run for long
+     * by looping or calling, so this is checked at loops and calls rather than every instruction */
+    fn check_interrupt(&self) -> VmResult<()> {
+        if self.interrupt.take() {
+            return Err(RuntimeError::Interrupted);
+        }
+
+        if self
+            .usage
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(RuntimeError::Timeout);
+        }
+
+        Ok(())
+    }
+
+    fn allocate(&mut self, bytes: usize) -> VmResult<()> {
+        allocate(
+            &self.limits,
+            &mut self.usage,
+            &self.globals,
+            &self.stack,
+            bytes,
+        )
+    }
+
+    fn execute(&mut self, mut frame: CallFrame) -> VmResult<()> {
+        loop {
+            self.step()?;
+            let opcode =
+                OpCode::try_from(frame.read_u8()?).map_err(|_| RuntimeError::MalformedProgram)?;
+
+            match opcode {
+                OpCode::Constant => {
+                    let value = match frame.read_constant()? {
+                        Constant::Number(value) => Value::Numeric(*value),
+                        Constant::String_(value) => Value::String_(value.clone()),
+                        Constant::Function(_) => return Err(RuntimeError::MalformedProgram),
+                    };
+
+                    self.stack.push(value);
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Boolean(true)),
+                OpCode::False => self.stack.push(Value::Boolean(false)),
+                OpCode::Pop => {
+                    self.pop()?;
+                }
+                OpCode::Dup => self.stack.push(self.peek(0)?.clone()),
+                OpCode::Dup2 => {
+                    let start = self.top(2)?;
+                    self.stack.extend_from_within(start..);
+                }
+                OpCode::Insert => {
+                    let depth = frame.read_u8()? as usize;
+                    let value = self.pop()?;
+
+                    let position = self.top(depth)?;
+                    self.stack.insert(position, value);
+                }
+                OpCode::GetLocal => {
+                    let slot = frame.read_u8()? as usize;
+                    let value = self.slot(frame.base + slot)?.clone();
+                    self.stack.push(value);
+                }
+                OpCode::SetLocal => {
+                    let slot = frame.read_u8()? as usize;
+                    let value = self.peek(0)?.clone();
+                    *self.slot(frame.base + slot)? = value;
+                }
+                OpCode::GetUpvalue => {
+                    let index = frame.read_u8()? as usize;
+
+                    let value = match &*upvalue(&frame, index)?.borrow() {
+                        Upvalue::Open(slot) => self.slot(*slot)?.clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = frame.read_u8()? as usize;
+                    let value = self.peek(0)?.clone();
+
+                    match &mut *upvalue(&frame, index)?.borrow_mut() {
+                        Upvalue::Open(slot) => *self.slot(*slot)? = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = frame.read_name()?;
+                    let value = self.pop()?;
+
+                    self.define_global(name, value)?;
+                }
+                OpCode::GetGlobal => {
+                    let name = frame.read_name()?;
+
+                    let value = self
+                        .globals
+                        .get(&name)
+                        .ok_or(RuntimeError::VariableDoesNotExist)?;
+
+                    self.stack.push(value.clone());
+                }
+                OpCode::SetGlobal => {
+                    let name = frame.read_name()?;
+                    let value = self.peek(0)?.clone();
+
+                    if self.constants.contains(&name) {
+                        return Err(RuntimeError::ConstantAssignment);
+                    }
+
+                    *self
+                        .globals
+                        .get_mut(&name)
+                        .ok_or(RuntimeError::VariableDoesNotExist)? = value;
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop()?;
+                    let object = self.pop()?;
+
+                    self.stack.push(get_index(object, index)?);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop()?;
+                    let index = self.pop()?;
+                    let object = self.pop()?;
+
+                    self.set_index(object, index, value.clone())?;
+                    self.stack.push(value);
+                }
+                OpCode::GetProperty => {
+                    frame.read_name()?;
+                    return Err(RuntimeError::UndefinedProperty);
+                }
+                OpCode::Equal
+                | OpCode::NotEqual
+                | OpCode::Greater
+                | OpCode::GreaterEqual
+                | OpCode::Less
+                | OpCode::LessEqual
+                | OpCode::Add
+                | OpCode::Subtract
+                | OpCode::Multiply
+                | OpCode::Divide
+                | OpCode::IntegerDivide
+                | OpCode::Modulo
+                | OpCode::Power => {
+                    let right = self.pop()?;
+                    let left = self.pop()?;
+                    let result = binary(opcode, left, right)?;
+
+                    if let Value::String_(string) = &result {
+                        self.allocate(string.len())?;
+                    }
+
+                    self.stack.push(result);
+                }
+                OpCode::Not => {
+                    let value = self.pop()?;
+                    self.stack.push(Value::Boolean(!value.is_truthy()));
+                }
+                OpCode::Negate => match self.pop()? {
+                    Value::Numeric(value) => self.stack.push(Value::Numeric(-value)),
+                    _ => return Err(RuntimeError::TypeError),
+                },
+                OpCode::Print => {
+                    let value = self.pop()?;
+                    writeln!(self.system.output(), "{value}").map_err(RuntimeError::Io)?;
+                }
+                OpCode::Jump => {
+                    let offset = frame.read_u16()? as usize;
+                    frame.ip += offset;
+                }
+                /* the condition is left on the stack, for `and` and `or` to yield */
+                OpCode::JumpIfFalse => {
+                    let offset = frame.read_u16()? as usize;
+
+                    if !self.peek(0)?.is_truthy() {
+                        frame.ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    self.check_interrupt()?;
+
+                    let offset = frame.read_u16()? as usize;
+                    frame.ip = frame
+                        .ip
+                        .checked_sub(offset)
+                        .ok_or(RuntimeError::MalformedProgram)?;
+                }
+                OpCode::Call => {
+                    self.check_interrupt()?;
+
+                    let count = frame.read_u8()? as usize;
+                    let callee = self.peek(count)?.clone();
+
+                    match callee {
+                        Value::Function(closure) => {
+                            if closure.prototype.arity != count {
+                                return Err(RuntimeError::WrongNumberOfArguments);
+                            }
+
+                            if self.frames.len() >= Self::MAX_FRAMES {
+                                return Err(RuntimeError::StackOverflow);
+                            }
+
+                            if self
+                                .limits
+                                .max_call_depth
+                                .is_some_and(|max_call_depth| self.frames.len() >= max_call_depth)
+                            {
+                                return Err(RuntimeError::CallDepthExceeded);
+                            }
+
+                            let base = self.stack.len() - count;
+                            let caller = std::mem::replace(
+                                &mut frame,
+                                CallFrame {
+                                    closure,
+                                    ip: 0,
+                                    base,
+                                },
+                            );
+
+                            self.frames.push(caller);
+                        }
+                        Value::NativeFunction(native) => {
+                            if !native.arity().accepts(count) {
+                                return Err(RuntimeError::WrongNumberOfArguments);
+                            }
+
+                            let arguments = self.stack.len() - count;
+                            let mut context = Context {
+                                system: &mut self.system,
+                                limits: &self.limits,
+                                usage: &mut self.usage,
+                                globals: &self.globals,
+                                stack: &self.stack,
+                            };
+                            let result = native.call(&mut context, &self.stack[arguments..])?;
+
+                            self.stack.truncate(arguments - 1);
+                            self.stack.push(result);
+                        }
+                        _ => return Err(RuntimeError::NotCallable),
+                    }
+                }
+                OpCode::Closure => {
+                    let Constant::Function(prototype) = frame.read_constant()?.clone() else {
+                        return Err(RuntimeError::MalformedProgram);
+                    };
+
+                    let upvalues = prototype
+                        .upvalues
+                        .iter()
+                        .map(|source| match *source {
+                            UpvalueSource::Local(slot) => {
+                                Ok(self.capture_upvalue(frame.base + slot as usize))
+                            }
+                            UpvalueSource::Upvalue(index) => {
+                                upvalue(&frame, index as usize).cloned()
+                            }
+                        })
+                        .collect::<VmResult<_>>()?;
+
+                    self.stack.push(Value::Function(Rc::new(Closure {
+                        prototype,
+                        upvalues,
+                    })));
+                }
+                OpCode::CloseUpvalue => {
+                    let slot = self.top(1)?;
+
+                    self.close_upvalues(slot)?;
+                    self.pop()?;
+                }
+                OpCode::Return => {
+                    let result = self.pop()?;
+
+                    self.close_upvalues(frame.base)?;
+                    self.stack.truncate(frame.base - 1);
+
+                    match self.frames.pop() {
+                        Some(caller) => {
+                            frame = caller;
+                            self.stack.push(result);
+                        }
+                        None => return Ok(()),
+                    }
+                }
+                OpCode::List => {
+                    let count = frame.read_u16()? as usize;
+                    let elements = self.stack.split_off(self.top(count)?);
+
+                    self.allocate(elements.len() * std::mem::size_of::<Value>())?;
+                    self.stack.push(Value::list(elements));
+                }
+                OpCode::Map => {
+                    let count = frame.read_u16()? as usize;
+                    let mut entries = self.stack.split_off(self.top(2 * count)?).into_iter();
+                    let mut map = IndexMap::with_capacity(count);
+
+                    while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+                        map.insert(MapKey::try_from(key)?, value);
+                    }
+
+                    self.allocate(
+                        map.len() * (std::mem::size_of::<MapKey>() + std::mem::size_of::<Value>()),
+                    )?;
+                    self.stack.push(Value::map(map));
+                }
+            }
+        }
+    }
+
+    fn set_index(&mut self, object: Value, index: Value, value: Value) -> VmResult<()> {
+        match object {
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let position = list_position(&list, index)?;
+
+                list[position] = value;
+                Ok(())
+            }
+            Value::Map(map) => {
+                let key = MapKey::try_from(index)?;
+
+                self.allocate(key.allocation() + std::mem::size_of::<Value>())?;
+                map.borrow_mut().insert(key, value);
+                Ok(())
+            }
+            _ => Err(RuntimeError::NotIndexable),
+        }
+    }
+
+    /* the stack is only ever too short for an instruction in a malformed program, which verifying
+     * it on load doesn't rule out, so every access to it is checked */
+    fn pop(&mut self) -> VmResult<Value> {
+        self.stack.pop().ok_or(RuntimeError::MalformedProgram)
+    }
+
+    fn peek(&self, distance: usize) -> VmResult<&Value> {
+        let slot = self.top(distance + 1)?;
+        Ok(&self.stack[slot])
+    }
+
+    /* the index of the lowest of the top `count` values */
+    fn top(&self, count: usize) -> VmResult<usize> {
+        self.stack
+            .len()
+            .checked_sub(count)
+            .ok_or(RuntimeError::MalformedProgram)
+    }
+
+    fn slot(&mut self, slot: usize) -> VmResult<&mut Value> {
+        self.stack
+            .get_mut(slot)
+            .ok_or(RuntimeError::MalformedProgram)
+    }
+
+    /* the open upvalue for a stack slot, shared with any closure that captured it before. a local
+     * function captures its own slot before the closure is pushed into it */
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let position = self
+            .open_upvalues
+            .partition_point(|upvalue| open_slot(upvalue) < slot);
+
+        match self.open_upvalues.get(position) {
+            Some(upvalue) if open_slot(upvalue) == slot => upvalue.clone(),
+            _ => {
+                let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+                self.open_upvalues.insert(position, upvalue.clone());
+                upvalue
+            }
+        }
+    }
+
+    /* moves the values of the slots from `from` upwards into the upvalues that capture them */
+    fn close_upvalues(&mut self, from: usize) -> VmResult<()> {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let slot = open_slot(upvalue);
+
+            if slot < from {
+                break;
+            }
+
+            let value = self.stack.get(slot).ok_or(RuntimeError::MalformedProgram)?;
+
+            *upvalue.borrow_mut() = Upvalue::Closed(value.clone());
+            self.open_upvalues.pop();
+        }
+
+        Ok(())
+    }
+}
+
+/* the standard library's names are known not to clash */
+impl Natives<Value> for Vm {
+    fn native(
+        &mut self,
+        name: &str,
+        arity: Arity,
+        function: impl Fn(&mut dyn Host, &[Value]) -> VmResult<Value> + 'static,
+    ) {
+        self.define_native(name, arity, function).unwrap();
+    }
+
+    fn constant(&mut self, name: &str, value: Value) {
+        self.define_global(Rc::from(name), value).unwrap();
+        self.constants.insert(Rc::from(name));
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Vm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Vm")
+            .field("globals", &self.globals)
+            .field("stack", &self.stack)
+            .finish_non_exhaustive()
+    }
+}
+
+/* accounts for `bytes` of newly allocated memory, measuring what the globals and the stack still
+ * reach once the estimate goes over the limit */
+fn allocate(
+    limits: &Limits,
+    usage: &mut Usage,
+    globals: &HashMap<Rc<str>, Value>,
+    stack: &[Value],
+    bytes: usize,
+) -> VmResult<()> {
+    let Some(max_memory) = limits.max_memory else {
+        return Ok(());
+    };
+
+    usage.memory += bytes;
+
+    if usage.memory > max_memory {
+        usage.memory = memory_usage(globals, stack);
+
+        if usage.memory > max_memory {
+            return Err(RuntimeError::MemoryLimitExceeded);
+        }
+    }
+
+    Ok(())
+}
+
+/* the approximate size of everything a program can still reach. every frame's closure sits on
+ * the stack below its arguments, and open upvalues point into the stack, so the globals and the
+ * stack are all the roots there are */
+fn memory_usage(globals: &HashMap<Rc<str>, Value>, stack: &[Value]) -> usize {
+    let mut walk = MemoryWalk::default();
+
+    for value in globals.values().chain(stack) {
+        walk.value(value);
+    }
+
+    walk.bytes
+}
+
+#[derive(Default)]
+struct MemoryWalk {
+    visited: HashSet<*const ()>,
+    bytes: usize,
+}
+
+impl MemoryWalk {
+    fn first_visit<T>(&mut self, shared: &Rc<T>) -> bool {
+        self.visited.insert(Rc::as_ptr(shared) as *const ())
+    }
+
+    fn value(&mut self, value: &Value) {
+        self.bytes += std::mem::size_of::<Value>();
+
+        match value {
+            Value::String_(string) => self.bytes += string.len(),
+            Value::List(list) => {
+                if self.first_visit(list) {
+                    for element in list.borrow().iter() {
+                        self.value(element);
+                    }
+                }
+            }
+            Value::Map(map) => {
+                if self.first_visit(map) {
+                    for (key, value) in map.borrow().iter() {
+                        self.bytes += key.allocation();
+                        self.value(value);
+                    }
+                }
+            }
+            Value::Function(closure) => {
+                if self.first_visit(closure) {
+                    for upvalue in &closure.upvalues {
+                        if let Upvalue::Closed(value) = &*upvalue.borrow() {
+                            self.value(value);
+                        }
+                    }
+                }
+            }
+            Value::Numeric(_) | Value::Boolean(_) | Value::NativeFunction(_) | Value::Nil => (),
+        }
+    }
+}
+
+fn upvalue(frame: &CallFrame, index: usize) -> VmResult<&Rc<RefCell<Upvalue>>> {
+    frame
+        .closure
+        .upvalues
+        .get(index)
+        .ok_or(RuntimeError::MalformedProgram)
+}
+
+fn open_slot(upvalue: &RefCell<Upvalue>) -> usize {
+    match *upvalue.borrow() {
+        Upvalue::Open(slot) => slot,
+        Upvalue::Closed(_) => unreachable!("closed upvalues are removed from the open list"),
+    }
+}
+
+/* converts an index value into a position within the bounds of the list */
+fn list_position(list: &[Value], index: Value) -> VmResult<usize> {
+    let Value::Numeric(index) = index else {
+        return Err(RuntimeError::InvalidIndex);
+    };
+
+    if index.fract() != 0.0 {
+        return Err(RuntimeError::InvalidIndex);
+    }
+
+    if index < 0.0 || index >= list.len() as f64 {
+        return Err(RuntimeError::IndexOutOfBounds);
+    }
+
+    Ok(index as usize)
+}
+
+fn get_index(object: Value, index: Value) -> VmResult<Value> {
+    match object {
+        Value::List(list) => {
+            let list = list.borrow();
+            let position = list_position(&list, index)?;
+
+            Ok(list[position].clone())
+        }
+        Value::Map(map) => map
+            .borrow()
+            .get(&MapKey::try_from(index)?)
+            .cloned()
+            .ok_or(RuntimeError::UndefinedKey),
+        _ => Err(RuntimeError::NotIndexable),
+    }
+}
+
+/* applies an arithmetic or comparison instruction to its operands */
+fn binary(opcode: OpCode, left: Value, right: Value) -> VmResult<Value> {
+    use Value::{Boolean, Numeric, String_};
+
+    match (opcode, left, right) {
+        (OpCode::Add, String_(left), String_(right)) => {
+            Ok(String_(Rc::from([&*left, &*right].concat())))
+        }
+        (OpCode::Equal, left, right) => Ok(Boolean(left == right)),
+        (OpCode::NotEqual, left, right) => Ok(Boolean(left != right)),
+        (opcode, Numeric(left), Numeric(right)) => Ok(match opcode {
+            OpCode::Greater => Boolean(left > right),
+            OpCode::GreaterEqual => Boolean(left >= right),
+            OpCode::Less => Boolean(left < right),
+            OpCode::LessEqual => Boolean(left <= right),
+            OpCode::Add => Numeric(left + right),
+            OpCode::Subtract => Numeric(left - right),
+            OpCode::Multiply => Numeric(left * right),
+            OpCode::Divide => Numeric(left / right),
+            OpCode::IntegerDivide if right == 0.0 => return Err(RuntimeError::DivisionByZero),
+            OpCode::IntegerDivide => Numeric((left / right).floor()),
+            OpCode::Modulo if right == 0.0 => return Err(RuntimeError::DivisionByZero),
+            OpCode::Modulo => {
+                /* floored modulo, like the evaluator's: the result takes the sign of the divisor */
+                let remainder = left % right;
+
+                if remainder != 0.0 && (remainder < 0.0) != (right < 0.0) {
+                    Numeric(remainder + right)
+                } else {
+                    Numeric(remainder)
+                }
+            }
+            OpCode::Power => Numeric(left.powf(right)),
+            _ => unreachable!("{opcode:?} is not a binary operator"),
+        }),
+        _ => Err(RuntimeError::TypeError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use evaluator::OutputBuffer;
+
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    fn run(vm: &mut Vm, code: &str) -> VmResult<()> {
+        let tokens = lexer::tokenize(code).unwrap();
+        let mut ast = parser::Ast::new(tokens.into_iter()).unwrap();
+        ast.resolve().unwrap();
+
+        vm.run(crate::compile(&ast).unwrap())
+    }
+
+    #[test]
+    fn test_globals_persist() {
+        let mut vm = Vm::new();
+
+        run(&mut vm, "var a = 1; fun f() { return a * 2; }").unwrap();
+        run(&mut vm, "a = f() + 1;").unwrap();
+
+        assert!(matches!(vm.global("a"), Some(Value::Numeric(3.0))));
+    }
+
+    #[test]
+    fn test_reusable_after_error() {
+        let mut vm = Vm::new();
+        let output = OutputBuffer::new();
+        vm.set_output(output.clone());
+
+        assert!(matches!(
+            run(
+                &mut vm,
+                "fun f(n) { var local = n; { return [local][1]; } } f(1);"
+            ),
+            Err(RuntimeError::IndexOutOfBounds)
+        ));
+        assert!(vm.stack.is_empty() && vm.frames.is_empty());
+
+        run(&mut vm, "{ var a = 1; print f; print a; }").unwrap();
+        assert_eq!(output.contents(), "<fn f>\n1\n");
+    }
+
+    fn limited(limits: Limits) -> Vm {
+        let mut vm = Vm::new();
+        vm.set_limits(limits);
+
+        vm
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        let mut vm = limited(Limits {
+            max_call_depth: None,
+            ..Limits::default()
+        });
+
+        run(
+            &mut vm,
+            "fun depth(n) { if (n == 0) return 0; return 1 + depth(n - 1); } var result = depth(100000);",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            vm.global("result"),
+            Some(Value::Numeric(100000.0))
+        ));
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut vm = limited(Limits {
+            max_call_depth: None,
+            ..Limits::default()
+        });
+
+        assert!(matches!(
+            run(&mut vm, "fun f(n) { return f(n + 1); } f(0);"),
+            Err(RuntimeError::StackOverflow)
+        ));
+        assert!(vm.stack.is_empty() && vm.frames.is_empty());
+
+        assert!(matches!(
+            run(&mut Vm::new(), "fun f(n) { return f(n + 1); } f(0);"),
+            Err(RuntimeError::CallDepthExceeded)
+        ));
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut vm = limited(Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        });
+
+        assert!(matches!(
+            run(&mut vm, "while (true) {}"),
+            Err(RuntimeError::StepLimitExceeded)
+        ));
+        for _ in 0..2 {
+            run(&mut vm, "for (var i = 0; i < 30; i = i + 1) {}").unwrap();
+        }
+
+        let mut vm = limited(Limits {
+            timeout: Some(Duration::from_millis(20)),
+            ..Limits::default()
+        });
+
+        assert!(matches!(
+            run(&mut vm, "fun spin() { while (true) {} } spin();"),
+            Err(RuntimeError::Timeout)
+        ));
+
+        let mut vm = limited(Limits {
+            max_call_depth: Some(50),
+            ..Limits::default()
+        });
+
+        assert!(matches!(
+            run(&mut vm, "fun f(n) { return f(n + 1); } f(0);"),
+            Err(RuntimeError::CallDepthExceeded)
+        ));
+        run(
+            &mut vm,
+            "fun g(n) { if (n > 0) return g(n - 1); return n; } g(49);",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_max_memory() {
+        let limits = Limits {
+            max_memory: Some(64 * 1024),
+            ..Limits::default()
+        };
+
+        for code in [
+            "var s = \"ab\"; while (true) s = s + s;",
+            "var l = []; while (true) push(l, [1, 2, 3]);",
+            "var m = {}; var i = 0; while (true) { m[i] = i; i = i + 1; }",
+        ] {
+            let mut vm = limited(limits);
+
+            assert!(matches!(
+                run(&mut vm, code),
+                Err(RuntimeError::MemoryLimitExceeded)
+            ));
+            assert!(vm.stack.is_empty() && vm.frames.is_empty());
+        }
+
+        /* memory that has been freed again doesn't count */
+        run(
+            &mut limited(limits),
+            "var s; for (var i = 0; i < 10000; i = i + 1) { s = \"abcdefghij\" + \"klmnopqrst\"; }",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut vm = Vm::new();
+        let handle = vm.interrupt_handle();
+        let done = Arc::new(AtomicBool::new(false));
+
+        /* an interrupt that lands before the run starts is forgotten, so keep interrupting until
+         * it has returned */
+        let interrupter = std::thread::spawn({
+            let done = done.clone();
+            move || {
+                while !done.load(Ordering::Relaxed) {
+                    handle.interrupt();
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        });
+
+        let result = run(&mut vm, "while (true) {}");
+        done.store(true, Ordering::Relaxed);
+        interrupter.join().unwrap();
+
+        assert!(matches!(result, Err(RuntimeError::Interrupted)));
+
+        /* an interrupt that arrives while nothing runs is forgotten */
+        vm.interrupt_handle().interrupt();
+        run(&mut vm, "for (var i = 0; i < 10; i = i + 1) {}").unwrap();
+
+        let handle = vm.interrupt_handle();
+        vm.define_native("stop", Arity::Exactly(0), move |_, _| {
+            handle.interrupt();
+            Ok(Value::Nil)
+        })
+        .unwrap();
+
+        assert!(matches!(
+            run(
+                &mut vm,
+                "var called = false; fun f() { called = true; } stop(); f();"
+            ),
+            Err(RuntimeError::Interrupted)
+        ));
+        assert!(matches!(vm.global("called"), Some(Value::Boolean(false))));
+    }
+
+    #[test]
+    fn test_native() {
+        let mut vm = Vm::new();
+
+        vm.define_native(
+            "twice",
+            Arity::Exactly(1),
+            |_, arguments| match &arguments[0] {
+                Value::Numeric(value) => Ok(Value::Numeric(value * 2.0)),
+                _ => Err(RuntimeError::TypeError),
+            },
+        )
+        .unwrap();
+
+        run(&mut vm, "var result = twice(21);").unwrap();
+        assert!(matches!(vm.global("result"), Some(Value::Numeric(42.0))));
+
+        assert!(matches!(
+            vm.define_native("len", Arity::Exactly(1), |_, _| Ok(Value::Nil)),
+            Err(RuntimeError::VariableRedefinition)
+        ));
+        assert!(matches!(
+            run(&mut vm, "twice(\"a\");"),
+            Err(RuntimeError::TypeError)
+        ));
+    }
+}
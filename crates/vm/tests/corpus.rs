@@ -0,0 +1,112 @@
+//! Runs every program in `tests/corpus` on both the tree-walking evaluator, also with its garbage
+//! collector running on every allocation, and the virtual machine, both freshly compiled and
+//! saved and loaded again. Both engines also run the program once it has been optimized. Each
+//! must print what the program's `.out` file holds, followed by the error the program stops
+//! with, if any. Both engines see the same console and clock: `eprint` writes to the program's
+//! output, `readLine` reads the lines of `INPUT`, and the time is always `NOW` seconds.
+
+use evaluator::{Evaluator, ManualClock, OutputBuffer};
+use vm::{Prototype, Vm};
+
+use std::io::Cursor;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+const INPUT: &str = "first line\nsecond line\n";
+const NOW: u64 = 1_000_000;
+
+fn parse(source: &str) -> parser::Ast {
+    let tokens = lexer::tokenize(source).unwrap();
+    let mut ast = parser::Ast::new(tokens.into_iter()).unwrap();
+    ast.resolve().unwrap();
+
+    ast
+}
+
+fn evaluate(ast: &parser::Ast, gc_stress: bool) -> String {
+    let mut evaluator = Evaluator::new();
+    let output = OutputBuffer::new();
+    evaluator.set_output(output.clone());
+    evaluator.set_error_output(output.clone());
+    evaluator.set_input(Cursor::new(INPUT));
+    evaluator.set_time_source(ManualClock::new(Duration::from_secs(NOW)));
+    evaluator.set_gc_stress(gc_stress);
+
+    match evaluator.evaluate(ast) {
+        Ok(()) => output.contents(),
+        Err(error) => format!("{}error: {error:?}\n", output.contents()),
+    }
+}
+
+fn run(prototype: Rc<Prototype>) -> String {
+    let mut vm = Vm::new();
+    let output = OutputBuffer::new();
+    vm.set_output(output.clone());
+    vm.set_error_output(output.clone());
+    vm.set_input(Cursor::new(INPUT));
+    vm.set_time_source(ManualClock::new(Duration::from_secs(NOW)));
+
+    match vm.run(prototype) {
+        Ok(()) => output.contents(),
+        Err(error) => format!("{}error: {error:?}\n", output.contents()),
+    }
+}
+
+#[test]
+fn test_corpus() {
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut programs = 0;
+
+    for entry in std::fs::read_dir(&corpus).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.extension().is_none_or(|extension| extension != "lox") {
+            continue;
+        }
+
+        let source = std::fs::read_to_string(&path).unwrap();
+        let ast = parse(&source);
+        let expected = std::fs::read_to_string(path.with_extension("out")).unwrap();
+
+        assert_eq!(
+            evaluate(&ast, false),
+            expected,
+            "evaluator on {}",
+            path.display()
+        );
+        assert_eq!(
+            evaluate(&ast, true),
+            expected,
+            "evaluator under gc stress on {}",
+            path.display()
+        );
+        let prototype = vm::compile(&ast).unwrap();
+        let mut bytes = Vec::new();
+        vm::save(&prototype, &mut bytes).unwrap();
+        let loaded = vm::load(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(run(prototype), expected, "vm on {}", path.display());
+        assert_eq!(run(loaded), expected, "loaded vm on {}", path.display());
+
+        let mut optimized = parse(&source);
+        optimized.optimize();
+
+        assert_eq!(
+            evaluate(&optimized, false),
+            expected,
+            "evaluator on optimized {}",
+            path.display()
+        );
+        assert_eq!(
+            run(vm::compile(&optimized).unwrap()),
+            expected,
+            "vm on optimized {}",
+            path.display()
+        );
+
+        programs += 1;
+    }
+
+    assert!(programs > 0);
+}